@@ -1,5 +1,6 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use std::fs::File;
+use pancakestack::bytecode::Bytecode;
+use std::fs::{self, File};
 use std::io::*;
 use std::str;
 
@@ -25,6 +26,34 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             assert_eq!(output, "\x04");
         })
     });
+
+    let program = fs::read_to_string("examples/long.pancake").unwrap();
+    let commands = pancakestack::parse_program_str(&program);
+    c.bench_function("run-long", |b| {
+        b.iter(|| {
+            let mut output_buf = Vec::new();
+            pancakestack::run_program(&commands, empty(), &mut output_buf).unwrap();
+            assert_eq!(output_buf, b"\x04");
+        })
+    });
+
+    let bytecode = Bytecode::compile(&commands);
+    c.bench_function("bytecode-long", |b| {
+        b.iter(|| {
+            let mut output_buf = Vec::new();
+            bytecode.run(empty(), &mut output_buf).unwrap();
+            assert_eq!(output_buf, b"\x04");
+        })
+    });
+
+    let optimized = pancakestack::optimize::optimize(&commands);
+    c.bench_function("optimized-long", |b| {
+        b.iter(|| {
+            let mut output_buf = Vec::new();
+            optimized.run(empty(), &mut output_buf).unwrap();
+            assert_eq!(output_buf, b"\x04");
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
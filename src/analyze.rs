@@ -0,0 +1,639 @@
+use crate::interpret::LabelSemantics;
+use crate::parse::Command;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{self, Display};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The maximum number of commands before a label that are searched for the value it is set to.
+const MAX_RUN: usize = 32;
+
+/// A likely mistake in a program found without running it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Lint {
+    /// The jump at the given index refers to a label that is never declared.
+    UndefinedLabel { command: usize, label: String },
+    /// The label at the given index has the same name as the label at `first`.
+    DuplicateLabel {
+        command: usize,
+        first: usize,
+        label: String,
+    },
+    /// No jump refers to the label at the given index.
+    UnusedLabel { command: usize, label: String },
+    /// The commands in the given range follow an [`EatAllOfThePancakes`](../parse/enum.Command.html#variant.EatAllOfThePancakes) and no jump leads to them.
+    UnreachableCode { commands: Range<usize> },
+}
+
+impl Lint {
+    /// Returns the code identifying the kind of this lint.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lint::UndefinedLabel { .. } => "W0001",
+            Lint::DuplicateLabel { .. } => "W0002",
+            Lint::UnusedLabel { .. } => "W0003",
+            Lint::UnreachableCode { .. } => "W0004",
+        }
+    }
+
+    /// Returns the index of the (first) command this lint is about.
+    #[must_use]
+    pub fn command(&self) -> usize {
+        match self {
+            Lint::UndefinedLabel { command, .. }
+            | Lint::DuplicateLabel { command, .. }
+            | Lint::UnusedLabel { command, .. } => *command,
+            Lint::UnreachableCode { commands } => commands.start,
+        }
+    }
+}
+impl Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::UndefinedLabel { label, .. } => write!(f, "jump to undefined label \"{label}\""),
+            Lint::DuplicateLabel { label, first, .. } => {
+                write!(
+                    f,
+                    "label \"{label}\" is already declared at command {first}"
+                )
+            }
+            Lint::UnusedLabel { label, .. } => write!(f, "label \"{label}\" is never jumped to"),
+            Lint::UnreachableCode { commands } if commands.len() == 1 => {
+                write!(f, "command {} can never run", commands.start)
+            }
+            Lint::UnreachableCode { commands } => write!(
+                f,
+                "commands {} to {} can never run",
+                commands.start,
+                commands.end - 1
+            ),
+        }
+    }
+}
+
+/// Checks the labels of the given program and reports jumps to undefined labels, labels declared more than once,
+/// labels that are never jumped to and code after [`EatAllOfThePancakes`](../parse/enum.Command.html#variant.EatAllOfThePancakes) that can never run.
+///
+/// With [`LabelSemantics::TopPancake`](../interpret/enum.LabelSemantics.html#variant.TopPancake) the position a label points to depends on the top pancake when it is declared.
+/// Code after [`EatAllOfThePancakes`](../parse/enum.Command.html#variant.EatAllOfThePancakes) is then only reported
+/// if the pancake of every label is built right before it, so that all positions jumps can lead to are known.
+///
+/// The lints are sorted by the index of the command they are about.
+/// ```rust
+/// use pancakestack::analyze::{lint, Lint};
+/// use pancakestack::LabelSemantics;
+///
+/// let program = pancakestack::parse_program_str("\
+/// Put this tasty pancake on top!
+/// [Loop]
+/// If the pancake is tasty, go over to \"Lop\".
+/// Eat all of the pancakes!
+/// Show me a pancake!");
+/// assert_eq!(
+///     lint(&program, LabelSemantics::TopPancake),
+///     [
+///         Lint::UnusedLabel { command: 1, label: "Loop".to_string() },
+///         Lint::UndefinedLabel { command: 2, label: "Lop".to_string() },
+///         Lint::UnreachableCode { commands: 4..5 },
+///     ]
+/// );
+/// ```
+#[must_use]
+pub fn lint(program: &[Command<'_>], label_semantics: LabelSemantics) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut declarations: HashMap<&str, usize> = HashMap::new();
+    for (index, command) in program.iter().enumerate() {
+        if let Command::Label(label) = command {
+            match declarations.get(label.as_ref()) {
+                Some(&first) => lints.push(Lint::DuplicateLabel {
+                    command: index,
+                    first,
+                    label: label.to_string(),
+                }),
+                None => {
+                    declarations.insert(label, index);
+                }
+            }
+        }
+    }
+
+    let mut used = vec![false; program.len()];
+    for (index, command) in program.iter().enumerate() {
+        if let Some(label) = jump_label(command) {
+            match declarations.get(label) {
+                Some(&declaration) => used[declaration] = true,
+                None => lints.push(Lint::UndefinedLabel {
+                    command: index,
+                    label: label.to_string(),
+                }),
+            }
+        }
+    }
+    for (label, &declaration) in &declarations {
+        if !used[declaration] {
+            lints.push(Lint::UnusedLabel {
+                command: declaration,
+                label: (*label).to_string(),
+            });
+        }
+    }
+
+    if let Some(reachable) = reachable_commands(program, label_semantics) {
+        let mut index = 0;
+        while index < program.len() {
+            if reachable[index] || index == 0 || program[index - 1] != Command::EatAllOfThePancakes
+            {
+                index += 1;
+                continue;
+            }
+            let start = index;
+            while index < program.len() && !reachable[index] {
+                index += 1;
+            }
+            lints.push(Lint::UnreachableCode {
+                commands: start..index,
+            });
+        }
+    }
+
+    lints.sort_by_key(Lint::command);
+    lints
+}
+
+/// Returns the label the given command jumps to, if it is a jump.
+pub(crate) fn jump_label<'a>(command: &'a Command<'_>) -> Option<&'a str> {
+    match command {
+        Command::IfThePancakeIsntTastyGoOverTo(label)
+        | Command::IfThePancakeIsTastyGoOverTo(label) => Some(label),
+        _ => None,
+    }
+}
+
+/// Where the execution can continue after a command.
+pub(crate) struct Edges {
+    /// Whether the execution can continue with the next command.
+    pub(crate) next: bool,
+    /// The positions the command can jump to.
+    pub(crate) targets: Vec<usize>,
+    /// Whether the command can jump to any position, because the position of its label is not known.
+    pub(crate) anywhere: bool,
+}
+
+/// Returns where the execution can continue after each command.
+///
+/// Jumps are assumed to be taken or not taken independently of the top pancake.
+/// Positions can be past the end of the program.
+pub(crate) fn edges(program: &[Command<'_>], label_semantics: LabelSemantics) -> Vec<Edges> {
+    let targets = label_targets(program, label_semantics);
+    let declared = program
+        .iter()
+        .filter_map(|command| match command {
+            Command::Label(label) => Some(label.as_ref()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    program
+        .iter()
+        .map(|command| {
+            let mut edges = Edges {
+                next: *command != Command::EatAllOfThePancakes,
+                targets: Vec::new(),
+                anywhere: false,
+            };
+            if let Some(label) = jump_label(command) {
+                match &targets {
+                    Some(targets) => {
+                        edges.targets = targets.get(label).cloned().unwrap_or_default();
+                    }
+                    None => edges.anywhere = declared.contains(label),
+                }
+            }
+            edges
+        })
+        .collect()
+}
+
+/// Returns for every command whether it can be executed, `None` if a jump can lead to a position that is not known.
+fn reachable_commands(
+    program: &[Command<'_>],
+    label_semantics: LabelSemantics,
+) -> Option<Vec<bool>> {
+    let edges = edges(program, label_semantics);
+    if edges.iter().any(|edges| edges.anywhere) {
+        return None;
+    }
+    let mut reachable = vec![false; program.len()];
+    let mut pending = vec![0];
+    while let Some(index) = pending.pop() {
+        if index >= program.len() || reachable[index] {
+            continue;
+        }
+        reachable[index] = true;
+        if edges[index].next {
+            pending.push(index + 1);
+        }
+        pending.extend(&edges[index].targets);
+    }
+    Some(reachable)
+}
+
+/// Returns the positions every label can point to, `None` if one of them is not known.
+///
+/// With [`LabelSemantics::NextCommand`](../interpret/enum.LabelSemantics.html#variant.NextCommand) every label points to the command after it.
+/// Otherwise the position of a label is known if the commands right before it build its pancake without depending on the pancakes below
+/// and no jump can lead between these commands.
+pub(crate) fn label_targets<'p>(
+    program: &'p [Command<'_>],
+    label_semantics: LabelSemantics,
+) -> Option<HashMap<&'p str, Vec<usize>>> {
+    let mut runs = Vec::new();
+    let mut targets: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, command) in program.iter().enumerate() {
+        if let Command::Label(label) = command {
+            if label_semantics == LabelSemantics::NextCommand {
+                targets.entry(label.as_ref()).or_default().push(index + 1);
+                continue;
+            }
+            let (start, top) = (index.saturating_sub(MAX_RUN)..index)
+                .rev()
+                .find_map(|start| Some((start, top_after(&program[start..index])?)))?;
+            if top == 0 {
+                return None;
+            }
+            runs.push(start + 1..index + 1);
+            targets
+                .entry(label.as_ref())
+                .or_default()
+                .push(top as usize - 1);
+        }
+    }
+    let jumps_into_run = targets
+        .values()
+        .flatten()
+        .any(|target| runs.iter().any(|run| run.contains(target)));
+    if jumps_into_run {
+        return None;
+    }
+    Some(targets)
+}
+
+/// Returns the top pancake after running the given commands on an unknown stack, if it does not depend on the stack.
+fn top_after(commands: &[Command<'_>]) -> Option<u32> {
+    // pancakes that depend on the stack or the input are None
+    let mut stack: Vec<Option<u32>> = Vec::new();
+    for command in commands {
+        match command {
+            Command::PutThisPancakeOnTop(adjective) => {
+                stack.push(Some(adjective.graphemes(true).count() as u32));
+            }
+            Command::EatThePancakeOnTop => {
+                stack.pop();
+            }
+            Command::PutTheTopPancakesTogether | Command::TakeFromTheTopPancakes => {
+                let first = stack.pop().flatten();
+                let second = stack.pop().flatten();
+                stack.push(match (first, second) {
+                    (Some(first), Some(second))
+                        if *command == Command::PutTheTopPancakesTogether =>
+                    {
+                        first.checked_add(second)
+                    }
+                    (Some(first), Some(second)) => first.checked_sub(second),
+                    _ => None,
+                });
+            }
+            Command::GiveMeAPancake | Command::HowAboutAHotcake => stack.push(None),
+            Command::FlipThePancakesOnTop => {
+                let first = stack.pop().flatten();
+                let second = stack.pop().flatten();
+                stack.push(first);
+                stack.push(second);
+            }
+            Command::PutAnotherPancakeOnTop => stack.push(stack.last().copied().flatten()),
+            Command::PutSyrupOnThePancakes | Command::TakeOffTheSyrup => {
+                for pancake in &mut stack {
+                    *pancake = pancake.and_then(|pancake| match command {
+                        Command::PutSyrupOnThePancakes => pancake.checked_add(1),
+                        _ => pancake.checked_sub(1),
+                    });
+                }
+            }
+            Command::PutButterOnThePancakes => {
+                let top = stack.pop().flatten();
+                stack.push(top.and_then(|top| top.checked_add(1)));
+            }
+            Command::TakeOffTheButter => {
+                let top = stack.pop().flatten();
+                stack.push(top.and_then(|top| top.checked_sub(1)));
+            }
+            Command::EatAllOfThePancakes => return None,
+            Command::ShowMeAPancake
+            | Command::Label(_)
+            | Command::IfThePancakeIsntTastyGoOverTo(_)
+            | Command::IfThePancakeIsTastyGoOverTo(_) => {}
+        }
+    }
+    stack.last().copied().flatten()
+}
+
+/// The maximum number of pancakes on the stack along the paths searched for a failing command.
+const MAX_PATH_DEPTH: usize = 64;
+
+/// How often the possible numbers of pancakes before a command can grow before they are assumed to grow without bound.
+const MAX_UPDATES: usize = 8;
+
+/// The possible numbers of pancakes on the stack before a command.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct StackDepth {
+    min: usize,
+    max: Option<usize>,
+}
+
+impl StackDepth {
+    /// Returns the smallest number of pancakes.
+    #[must_use]
+    pub fn min(&self) -> usize {
+        self.min
+    }
+
+    /// Returns the largest number of pancakes, `None` if the stack can grow without bound.
+    #[must_use]
+    pub fn max(&self) -> Option<usize> {
+        self.max
+    }
+
+    /// Returns the depths that are possible before either this or the given depths.
+    fn join(self, other: StackDepth) -> StackDepth {
+        StackDepth {
+            min: self.min.min(other.min),
+            max: self.max.and_then(|max| Some(max.max(other.max?))),
+        }
+    }
+}
+
+/// A command that fails with [`OutOfPancakes`](../interpret/enum.Error.html#variant.OutOfPancakes) if it is executed with too few pancakes on the stack.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StackProblem {
+    command: usize,
+    required: usize,
+    depth: StackDepth,
+    path: Vec<usize>,
+}
+
+impl StackProblem {
+    /// Returns the index of the command.
+    #[must_use]
+    pub fn command(&self) -> usize {
+        self.command
+    }
+
+    /// Returns the number of pancakes the command needs.
+    #[must_use]
+    pub fn required(&self) -> usize {
+        self.required
+    }
+
+    /// Returns the possible numbers of pancakes before the command.
+    #[must_use]
+    pub fn depth(&self) -> StackDepth {
+        self.depth
+    }
+
+    /// Returns whether the command fails every time it is executed.
+    #[must_use]
+    pub fn always_fails(&self) -> bool {
+        self.depth.max.is_some_and(|max| max < self.required)
+    }
+
+    /// Returns the indices of the commands executed from the start of the program up to the failing command.
+    ///
+    /// The path is empty if the stack has to grow larger than 64 pancakes before the command can fail.
+    #[must_use]
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+}
+impl Display for StackProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pancakes = match self.required {
+            1 => "a pancake".to_string(),
+            required => format!("{required} pancakes"),
+        };
+        if self.always_fails() {
+            write!(
+                f,
+                "command {} needs {pancakes} but there are at most {}",
+                self.command,
+                self.depth.max.unwrap_or_default()
+            )
+        } else {
+            write!(
+                f,
+                "command {} needs {pancakes} but there may be only {}",
+                self.command, self.depth.min
+            )
+        }
+    }
+}
+
+/// The numbers of pancakes on the stack during the execution of a program, see [`analyze_stack`](./fn.analyze_stack.html).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StackAnalysis {
+    depths: Vec<Option<StackDepth>>,
+    problems: Vec<StackProblem>,
+}
+
+impl StackAnalysis {
+    /// Returns the possible numbers of pancakes before the command at the given index, `None` if it is never executed.
+    #[must_use]
+    pub fn depth(&self, command: usize) -> Option<StackDepth> {
+        self.depths.get(command).copied().flatten()
+    }
+
+    /// Returns the commands that can fail because there are too few pancakes, sorted by their index.
+    #[must_use]
+    pub fn problems(&self) -> &[StackProblem] {
+        &self.problems
+    }
+}
+
+/// Computes the smallest and largest number of pancakes on the stack before every command
+/// and reports the commands that can be executed with too few pancakes, together with a path leading there.
+///
+/// Jumps are assumed to be possibly taken regardless of the top pancake, and jumps to labels whose position is not known
+/// (see [`lint`](./fn.lint.html)) are assumed to possibly lead to any command.
+/// So a reported command does not necessarily fail, but a command that is not reported never fails with
+/// [`OutOfPancakes`](../interpret/enum.Error.html#variant.OutOfPancakes).
+/// ```rust
+/// let program = pancakestack::parse_program_str("\
+/// Give me a pancake!
+/// [Loop]
+/// Show me a pancake!
+/// Put the top pancakes together!");
+/// let analysis = pancakestack::analyze::analyze_stack(&program, pancakestack::LabelSemantics::TopPancake);
+/// assert_eq!(analysis.depth(3).unwrap().min(), 1);
+///
+/// let problem = &analysis.problems()[0];
+/// assert_eq!(problem.command(), 3);
+/// assert!(problem.always_fails());
+/// assert_eq!(problem.path(), [0, 1, 2, 3]);
+/// ```
+#[must_use]
+pub fn analyze_stack(program: &[Command<'_>], label_semantics: LabelSemantics) -> StackAnalysis {
+    let edges = edges(program, label_semantics);
+    let mut depths: Vec<Option<StackDepth>> = vec![None; program.len()];
+    let mut updates = vec![0; program.len()];
+    let mut pending = Vec::new();
+    if !program.is_empty() {
+        depths[0] = Some(StackDepth {
+            min: 0,
+            max: Some(0),
+        });
+        pending.push(0);
+    }
+    while let Some(index) = pending.pop() {
+        let depth = depths[index].expect("pending commands are executed");
+        let (required, effect) = stack_effect(&program[index], label_semantics);
+        if depth.max.is_some_and(|max| max < required) {
+            // the command always fails
+            continue;
+        }
+        let after = StackDepth {
+            min: (depth.min.max(required) as isize + effect) as usize,
+            max: depth.max.map(|max| (max as isize + effect) as usize),
+        };
+        let edges = &edges[index];
+        let successors: Box<dyn Iterator<Item = usize>> = if edges.anywhere {
+            Box::new(0..program.len())
+        } else {
+            Box::new(
+                edges
+                    .targets
+                    .iter()
+                    .copied()
+                    .chain(Some(index + 1).filter(|_| edges.next)),
+            )
+        };
+        for successor in successors.filter(|&successor| successor < program.len()) {
+            let joined = match depths[successor] {
+                Some(previous) => {
+                    let mut joined = previous.join(after);
+                    if joined == previous {
+                        continue;
+                    }
+                    updates[successor] += 1;
+                    if updates[successor] > MAX_UPDATES && joined.max != previous.max {
+                        joined.max = None;
+                    }
+                    joined
+                }
+                None => after,
+            };
+            depths[successor] = Some(joined);
+            pending.push(successor);
+        }
+    }
+
+    let mut paths = failing_paths(program, &edges, label_semantics);
+    let problems = depths
+        .iter()
+        .enumerate()
+        .filter_map(|(index, depth)| {
+            let depth = (*depth)?;
+            let (required, _) = stack_effect(&program[index], label_semantics);
+            if depth.min >= required {
+                return None;
+            }
+            Some(StackProblem {
+                command: index,
+                required,
+                depth,
+                path: paths.remove(&index).unwrap_or_default(),
+            })
+        })
+        .collect();
+    StackAnalysis { depths, problems }
+}
+
+/// Returns the shortest paths from the start of the program to every command that can be executed with too few pancakes,
+/// only considering paths along which there are at most [`MAX_PATH_DEPTH`](./constant.MAX_PATH_DEPTH.html) pancakes.
+fn failing_paths(
+    program: &[Command<'_>],
+    edges: &[Edges],
+    label_semantics: LabelSemantics,
+) -> HashMap<usize, Vec<usize>> {
+    let states = MAX_PATH_DEPTH + 1;
+    let mut parents: Vec<Option<usize>> = vec![None; program.len() * states];
+    let mut visited = vec![false; program.len() * states];
+    let mut anywhere_visited = vec![false; states];
+    let mut pending = VecDeque::new();
+    let mut paths = HashMap::new();
+    if !program.is_empty() {
+        visited[0] = true;
+        pending.push_back(0);
+    }
+    while let Some(state) = pending.pop_front() {
+        let (index, depth) = (state / states, state % states);
+        let (required, effect) = stack_effect(&program[index], label_semantics);
+        if depth < required {
+            paths.entry(index).or_insert_with(|| {
+                let mut path = vec![index];
+                let mut state = state;
+                while let Some(parent) = parents[state] {
+                    path.push(parent / states);
+                    state = parent;
+                }
+                path.reverse();
+                path
+            });
+            continue;
+        }
+        let depth = (depth as isize + effect) as usize;
+        if depth > MAX_PATH_DEPTH {
+            continue;
+        }
+        let edges = &edges[index];
+        let mut successors = edges.targets.clone();
+        if edges.next {
+            successors.push(index + 1);
+        }
+        if edges.anywhere && !anywhere_visited[depth] {
+            anywhere_visited[depth] = true;
+            successors.extend(0..program.len());
+        }
+        for successor in successors {
+            let successor_state = successor * states + depth;
+            if successor < program.len() && !visited[successor_state] {
+                visited[successor_state] = true;
+                parents[successor_state] = Some(state);
+                pending.push_back(successor_state);
+            }
+        }
+    }
+    paths
+}
+
+/// Returns the number of pancakes the given command needs and by how many it changes the number of pancakes.
+fn stack_effect(command: &Command<'_>, label_semantics: LabelSemantics) -> (usize, isize) {
+    match command {
+        // the label points to the command after it instead of reading the top pancake
+        Command::Label(_) if label_semantics == LabelSemantics::NextCommand => (0, 0),
+        Command::PutThisPancakeOnTop(_) | Command::GiveMeAPancake | Command::HowAboutAHotcake => {
+            (0, 1)
+        }
+        Command::PutAnotherPancakeOnTop => (1, 1),
+        Command::EatThePancakeOnTop => (1, -1),
+        Command::PutTheTopPancakesTogether | Command::TakeFromTheTopPancakes => (2, -1),
+        Command::FlipThePancakesOnTop => (2, 0),
+        Command::ShowMeAPancake
+        | Command::Label(_)
+        | Command::IfThePancakeIsntTastyGoOverTo(_)
+        | Command::IfThePancakeIsTastyGoOverTo(_)
+        | Command::PutButterOnThePancakes
+        | Command::TakeOffTheButter => (1, 0),
+        Command::PutSyrupOnThePancakes
+        | Command::TakeOffTheSyrup
+        | Command::EatAllOfThePancakes => (0, 0),
+    }
+}
@@ -0,0 +1,385 @@
+use crate::parse::Command;
+use std::fmt::{self, Display};
+
+/// The number of cells of the tape used by [`compile`](./fn.compile.html).
+pub const DEFAULT_TAPE_LENGTH: usize = 256;
+
+/// Compiles the given Brainfuck source into a Pancake Stack program with a tape of [`DEFAULT_TAPE_LENGTH`](./constant.DEFAULT_TAPE_LENGTH.html) cells.
+/// See [`compile_with_tape_length`](./fn.compile_with_tape_length.html).
+///
+/// # Errors
+/// Will return `Err` if the brackets of the source are not balanced.
+pub fn compile(source: &str) -> Result<Vec<Command<'static>>, BrainfuckError> {
+    compile_with_tape_length(source, DEFAULT_TAPE_LENGTH)
+}
+
+/// Compiles the given Brainfuck source into a Pancake Stack program with a tape of the given number of cells.
+///
+/// Cells hold values from 0 to 255 and wrap around, reading at the end of the input stores 0.
+/// Every cell value written is printed as the char with that code, so values above 127 are written encoded as UTF-8.
+/// Moving the head outside of the tape makes the program fail with [`PancakeUnderflow`](../interpret/enum.Error.html#variant.PancakeUnderflow).
+///
+/// As the pancake stack can only be accessed from the top, the cells are kept in labels:
+/// the label of a cell points into a table of commands pushing each possible value, so that jumping to it loads the value of the cell.
+/// The position of the head and the value of the current cell are kept on the stack.
+/// ```rust
+/// let program = pancakestack::brainfuck::compile("++++++++[>++++++++<-]>+.").unwrap();
+/// let mut output = Vec::new();
+/// pancakestack::run_program(&program, std::io::empty(), &mut output).unwrap();
+/// assert_eq!(output, b"A");
+/// ```
+///
+/// # Errors
+/// Will return `Err` if the brackets of the source are not balanced.
+pub fn compile_with_tape_length(
+    source: &str,
+    tape_length: usize,
+) -> Result<Vec<Command<'static>>, BrainfuckError> {
+    let instructions = parse(source)?;
+    let tape_length = tape_length.max(1);
+    // constants are encoded with a fixed number of digits, so that they can be filled in once all positions are known
+    let mut digits = 2;
+    loop {
+        let mut compiler = Compiler::new(tape_length, digits);
+        compiler.compile(&instructions);
+        match compiler.finish() {
+            Some(program) => return Ok(program),
+            None => digits += 1,
+        }
+    }
+}
+
+/// A run of Brainfuck instructions with the same effect.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Instruction {
+    /// Adds the given value to the current cell, wrapping around.
+    Add(u8),
+    /// Moves the head by the given number of cells.
+    Move(isize),
+    Output,
+    Input,
+    /// Starts the loop with the given index.
+    Open(usize),
+    /// Ends the loop with the given index.
+    Close(usize),
+}
+
+fn parse(source: &str) -> Result<Vec<Instruction>, BrainfuckError> {
+    let mut instructions = Vec::new();
+    let mut open_loops = Vec::new();
+    let mut loops = 0;
+    for (position, c) in source.char_indices() {
+        let instruction = match c {
+            '+' => Instruction::Add(1),
+            '-' => Instruction::Add(255),
+            '>' => Instruction::Move(1),
+            '<' => Instruction::Move(-1),
+            '.' => Instruction::Output,
+            ',' => Instruction::Input,
+            '[' => {
+                open_loops.push((loops, position));
+                loops += 1;
+                Instruction::Open(loops - 1)
+            }
+            ']' => match open_loops.pop() {
+                Some((index, _)) => Instruction::Close(index),
+                None => return Err(BrainfuckError::UnmatchedClosingBracket(position)),
+            },
+            _ => continue,
+        };
+        match (instructions.last_mut(), instruction) {
+            (Some(Instruction::Add(value)), Instruction::Add(addend)) => {
+                *value = value.wrapping_add(addend);
+            }
+            (Some(Instruction::Move(offset)), Instruction::Move(step)) => *offset += step,
+            _ => instructions.push(instruction),
+        }
+    }
+    if let Some((_, position)) = open_loops.pop() {
+        return Err(BrainfuckError::UnmatchedOpeningBracket(position));
+    }
+    Ok(instructions)
+}
+
+/// A position in the generated program that is only known once the whole program is generated.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Address {
+    /// The table pushing the values of the cells, 3 commands per value.
+    Values,
+    /// The table loading the value of each cell, 3 commands per cell.
+    Loads,
+    /// The table storing the value of each cell, 4 commands per cell.
+    Stores,
+    /// The position marked with the given index.
+    Mark(usize),
+}
+
+/// The digits of constants are adjective lengths, so each digit is at most 31 chars long.
+const DIGIT_BITS: u32 = 5;
+
+struct Compiler {
+    tape_length: usize,
+    digits: usize,
+    program: Vec<Command<'static>>,
+    /// The positions of the digits of the constants in the program, together with the address plus offset they have to push.
+    constants: Vec<(Vec<usize>, Address, usize)>,
+    marks: Vec<usize>,
+    values: usize,
+    loads: usize,
+    stores: usize,
+}
+
+impl Compiler {
+    fn new(tape_length: usize, digits: usize) -> Self {
+        Compiler {
+            tape_length,
+            digits,
+            program: Vec::new(),
+            constants: Vec::new(),
+            marks: Vec::new(),
+            values: 0,
+            loads: 0,
+            stores: 0,
+        }
+    }
+
+    fn compile(&mut self, instructions: &[Instruction]) {
+        let loops = instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::Open(_)))
+            .count();
+        // marks 2i and 2i+1 are the positions after the start and the end of the loop i
+        self.marks = vec![0; loops * 2];
+
+        for index in 0..loops {
+            self.set_label(&format!("while {index}"), Address::Mark(index * 2));
+            self.set_label(&format!("end {index}"), Address::Mark(index * 2 + 1));
+        }
+        self.constant(Address::Values, 1);
+        for cell in 0..self.tape_length {
+            self.emit(Command::Label(format!("cell {cell}").into()));
+        }
+        self.emit(Command::EatThePancakeOnTop);
+        // the head and the current cell
+        self.push(0);
+        self.push(0);
+
+        for instruction in instructions {
+            match *instruction {
+                Instruction::Add(0) | Instruction::Move(0) => {}
+                Instruction::Add(value) => {
+                    self.push(u32::from(value));
+                    self.emit(Command::PutTheTopPancakesTogether);
+                    self.wrap_around();
+                }
+                Instruction::Move(offset) => self.move_head(offset),
+                Instruction::Output => self.emit(Command::ShowMeAPancake),
+                Instruction::Input => {
+                    self.emit(Command::EatThePancakeOnTop);
+                    self.emit(Command::HowAboutAHotcake);
+                }
+                Instruction::Open(index) => {
+                    self.emit(Command::IfThePancakeIsntTastyGoOverTo(
+                        format!("end {index}").into(),
+                    ));
+                    self.marks[index * 2] = self.program.len();
+                }
+                Instruction::Close(index) => {
+                    self.emit(Command::IfThePancakeIsTastyGoOverTo(
+                        format!("while {index}").into(),
+                    ));
+                    self.marks[index * 2 + 1] = self.program.len();
+                }
+            }
+        }
+        self.emit(Command::EatAllOfThePancakes);
+
+        // entered with the position of the entry on top, pushes the value and returns
+        self.values = self.program.len();
+        for value in 0..512 {
+            let value = value % 256;
+            self.emit(Command::EatThePancakeOnTop);
+            self.push(value);
+            self.emit(if value == 0 {
+                Command::IfThePancakeIsntTastyGoOverTo("return".into())
+            } else {
+                Command::IfThePancakeIsTastyGoOverTo("return".into())
+            });
+        }
+        // entered with the head and the position of the entry on top, continues with the value of the cell
+        self.loads = self.program.len();
+        for cell in 0..self.tape_length {
+            self.emit(Command::EatThePancakeOnTop);
+            self.push(1);
+            self.emit(Command::IfThePancakeIsTastyGoOverTo(
+                format!("cell {cell}").into(),
+            ));
+        }
+        // entered with the position of the value, the head and the position of the entry on top
+        self.stores = self.program.len();
+        for cell in 0..self.tape_length {
+            self.emit(Command::EatThePancakeOnTop);
+            self.emit(Command::FlipThePancakesOnTop);
+            self.emit(Command::Label(format!("cell {cell}").into()));
+            self.emit(Command::IfThePancakeIsTastyGoOverTo("return".into()));
+        }
+    }
+
+    /// Replaces the current cell by its value modulo 256, it has to be less than 512.
+    fn wrap_around(&mut self) {
+        let mark = self.mark();
+        self.set_label("return", Address::Mark(mark));
+        self.multiply(3);
+        self.jump(Address::Values);
+        self.marks[mark] = self.program.len();
+    }
+
+    fn move_head(&mut self, offset: isize) {
+        // store the value of the current cell as the position of its entry in the values table
+        let store = self.mark();
+        self.multiply(3);
+        self.constant(Address::Values, 1);
+        self.emit(Command::PutTheTopPancakesTogether);
+        self.emit(Command::FlipThePancakesOnTop);
+        self.set_label("return", Address::Mark(store));
+        self.emit(Command::PutAnotherPancakeOnTop);
+        self.multiply(4);
+        self.jump(Address::Stores);
+        self.marks[store] = self.program.len();
+        self.emit(Command::EatThePancakeOnTop);
+
+        self.push(offset.unsigned_abs() as u32);
+        if offset < 0 {
+            // fails with an underflow when moving left of the first cell
+            self.emit(Command::FlipThePancakesOnTop);
+            self.emit(Command::TakeFromTheTopPancakes);
+        } else {
+            self.emit(Command::PutTheTopPancakesTogether);
+            // fails with an underflow when moving right of the last cell
+            self.emit(Command::PutAnotherPancakeOnTop);
+            self.push((self.tape_length - 1) as u32);
+            self.emit(Command::TakeFromTheTopPancakes);
+            self.emit(Command::EatThePancakeOnTop);
+        }
+
+        let load = self.mark();
+        self.set_label("return", Address::Mark(load));
+        self.emit(Command::PutAnotherPancakeOnTop);
+        self.multiply(3);
+        self.jump(Address::Loads);
+        self.marks[load] = self.program.len();
+    }
+
+    /// Replaces the top pancake by the entry it selects in the table at the given address and jumps there.
+    fn jump(&mut self, table: Address) {
+        self.constant(table, 1);
+        self.emit(Command::PutTheTopPancakesTogether);
+        self.emit(Command::Label("jump".into()));
+        self.emit(Command::IfThePancakeIsTastyGoOverTo("jump".into()));
+    }
+
+    /// Multiplies the top pancake by 3 or 4.
+    fn multiply(&mut self, factor: u32) {
+        if factor == 3 {
+            self.emit(Command::PutAnotherPancakeOnTop);
+            self.emit(Command::PutAnotherPancakeOnTop);
+            self.emit(Command::PutTheTopPancakesTogether);
+            self.emit(Command::PutTheTopPancakesTogether);
+        } else {
+            self.emit(Command::PutAnotherPancakeOnTop);
+            self.emit(Command::PutTheTopPancakesTogether);
+            self.emit(Command::PutAnotherPancakeOnTop);
+            self.emit(Command::PutTheTopPancakesTogether);
+        }
+    }
+
+    /// Points the given label at the given address without changing the stack.
+    fn set_label(&mut self, label: &str, address: Address) {
+        self.constant(address, 1);
+        self.emit(Command::Label(label.to_string().into()));
+        self.emit(Command::EatThePancakeOnTop);
+    }
+
+    fn mark(&mut self) -> usize {
+        self.marks.push(0);
+        self.marks.len() - 1
+    }
+
+    fn emit(&mut self, command: Command<'static>) {
+        self.program.push(command);
+    }
+
+    fn push(&mut self, value: u32) {
+        self.emit(Command::PutThisPancakeOnTop(
+            adjective(value as usize).into(),
+        ));
+    }
+
+    /// Emits commands pushing the given address plus offset once it is known.
+    /// Every digit is pushed and added to the previous ones shifted by [`DIGIT_BITS`](./constant.DIGIT_BITS.html).
+    fn constant(&mut self, address: Address, offset: usize) {
+        let mut digits = vec![self.program.len()];
+        self.push(0);
+        for _ in 1..self.digits {
+            for _ in 0..DIGIT_BITS {
+                self.emit(Command::PutAnotherPancakeOnTop);
+                self.emit(Command::PutTheTopPancakesTogether);
+            }
+            digits.push(self.program.len());
+            self.push(0);
+            self.emit(Command::PutTheTopPancakesTogether);
+        }
+        self.constants.push((digits, address, offset));
+    }
+
+    /// Fills in the constants, returns `None` if one of them does not fit into the number of digits.
+    fn finish(mut self) -> Option<Vec<Command<'static>>> {
+        let digit_count = 1 << DIGIT_BITS;
+        for (digits, address, offset) in std::mem::take(&mut self.constants) {
+            let mut value = offset
+                + match address {
+                    Address::Values => self.values,
+                    Address::Loads => self.loads,
+                    Address::Stores => self.stores,
+                    Address::Mark(index) => self.marks[index],
+                };
+            for &index in digits.iter().rev() {
+                self.program[index] =
+                    Command::PutThisPancakeOnTop(adjective(value % digit_count).into());
+                value /= digit_count;
+            }
+            if value != 0 {
+                return None;
+            }
+        }
+        Some(self.program)
+    }
+}
+
+/// Returns an adjective with the given number of chars.
+fn adjective(length: usize) -> String {
+    "fluffy".chars().cycle().take(length).collect()
+}
+
+/// An enum representing the possible errors when compiling Brainfuck source.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BrainfuckError {
+    /// The `[` at the given byte offset has no matching `]`.
+    UnmatchedOpeningBracket(usize),
+    /// The `]` at the given byte offset has no matching `[`.
+    UnmatchedClosingBracket(usize),
+}
+
+impl Display for BrainfuckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BrainfuckError::UnmatchedOpeningBracket(position) => {
+                write!(f, "unmatched '[' at byte {position}")
+            }
+            BrainfuckError::UnmatchedClosingBracket(position) => {
+                write!(f, "unmatched ']' at byte {position}")
+            }
+        }
+    }
+}
+impl std::error::Error for BrainfuckError {}
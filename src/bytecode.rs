@@ -0,0 +1,241 @@
+use crate::interpret::{
+    eat, flip, label_position, parse_pancake, put_another, put_butter, put_syrup, put_together,
+    show, take_from, take_off_butter, take_off_syrup, top, Error,
+};
+use crate::parse::Command;
+use std::collections::HashMap;
+use std::io::{prelude::*, BufReader};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A single instruction of a compiled program.
+///
+/// Instructions of a [compiled](./struct.Bytecode.html#method.compile) program correspond to the command at the same position.
+/// The fused instructions are only produced by the [optimizer](../optimize/fn.optimize.html).
+///
+/// Labels are identified by slots instead of their names, see [`Bytecode::label_name`](./struct.Bytecode.html#method.label_name).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Op {
+    /// Pushes the given value. See [`PutThisPancakeOnTop`](../parse/enum.Command.html#variant.PutThisPancakeOnTop).
+    Push(u32),
+    /// See [`EatThePancakeOnTop`](../parse/enum.Command.html#variant.EatThePancakeOnTop).
+    Pop,
+    /// See [`PutTheTopPancakesTogether`](../parse/enum.Command.html#variant.PutTheTopPancakesTogether).
+    Add,
+    /// See [`GiveMeAPancake`](../parse/enum.Command.html#variant.GiveMeAPancake).
+    ReadNumber,
+    /// See [`HowAboutAHotcake`](../parse/enum.Command.html#variant.HowAboutAHotcake).
+    ReadByte,
+    /// See [`ShowMeAPancake`](../parse/enum.Command.html#variant.ShowMeAPancake).
+    Print,
+    /// See [`TakeFromTheTopPancakes`](../parse/enum.Command.html#variant.TakeFromTheTopPancakes).
+    Sub,
+    /// See [`FlipThePancakesOnTop`](../parse/enum.Command.html#variant.FlipThePancakesOnTop).
+    Swap,
+    /// See [`PutAnotherPancakeOnTop`](../parse/enum.Command.html#variant.PutAnotherPancakeOnTop).
+    Dup,
+    /// Sets the position of the label in the given slot. See [`Label`](../parse/enum.Command.html#variant.Label).
+    Label(usize),
+    /// Jumps to the label in the given slot if the top value is zero. See [`IfThePancakeIsntTastyGoOverTo`](../parse/enum.Command.html#variant.IfThePancakeIsntTastyGoOverTo).
+    Jz(usize),
+    /// Jumps to the label in the given slot if the top value is not zero. See [`IfThePancakeIsTastyGoOverTo`](../parse/enum.Command.html#variant.IfThePancakeIsTastyGoOverTo).
+    Jnz(usize),
+    /// See [`PutSyrupOnThePancakes`](../parse/enum.Command.html#variant.PutSyrupOnThePancakes).
+    IncrementAll,
+    /// See [`PutButterOnThePancakes`](../parse/enum.Command.html#variant.PutButterOnThePancakes).
+    Increment,
+    /// See [`TakeOffTheSyrup`](../parse/enum.Command.html#variant.TakeOffTheSyrup).
+    DecrementAll,
+    /// See [`TakeOffTheButter`](../parse/enum.Command.html#variant.TakeOffTheButter).
+    Decrement,
+    /// See [`EatAllOfThePancakes`](../parse/enum.Command.html#variant.EatAllOfThePancakes).
+    Halt,
+    /// Adds the given value to the top value, like a sequence of [`Increment`](#variant.Increment)s or [`Decrement`](#variant.Decrement)s.
+    AddToTop(i64),
+    /// Removes the given number of values, like a sequence of [`Pop`](#variant.Pop)s.
+    Drop(usize),
+    /// Fails with [`OutOfPancakes`](../interpret/enum.Error.html#variant.OutOfPancakes) if there are less than the given number of values but does nothing otherwise.
+    RequireDepth(usize),
+    /// Continues with the op at the given index.
+    Goto(usize),
+}
+
+/// A program compiled to [`Op`](./enum.Op.html)s, with adjective lengths computed and label names replaced by slots.
+///
+/// Labels point to the position given by the top pancake when they are declared, as with [`LabelSemantics::TopPancake`](../interpret/enum.LabelSemantics.html#variant.TopPancake).
+/// Programs relying on [`LabelSemantics::NextCommand`](../interpret/enum.LabelSemantics.html#variant.NextCommand) have to be run by a [`Machine`](../interpret/struct.Machine.html).
+/// ```rust
+/// use pancakestack::bytecode::{Bytecode, Op};
+///
+/// let program = pancakestack::parse_program_str("Put this good pancake on top!\n[Loop]\nShow me a pancake!");
+/// let bytecode = Bytecode::compile(&program);
+/// assert_eq!(bytecode.ops(), &[Op::Push(4), Op::Label(0), Op::Print]);
+/// assert_eq!(bytecode.label_name(0), Some("Loop"));
+///
+/// let mut output = Vec::new();
+/// bytecode.run(std::io::empty(), &mut output).unwrap();
+/// assert_eq!(output, b"\x04");
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Bytecode {
+    pub(crate) ops: Vec<Op>,
+    pub(crate) labels: Vec<String>,
+    pub(crate) entries: Vec<usize>,
+}
+
+impl Bytecode {
+    /// Compiles the given program for [`LabelSemantics::TopPancake`](../interpret/enum.LabelSemantics.html#variant.TopPancake).
+    /// Every label name gets a slot, in the order the names first appear in the program.
+    #[must_use]
+    pub fn compile(program: &[Command<'_>]) -> Self {
+        let mut slots = HashMap::new();
+        let mut labels = Vec::new();
+        let mut slot = |label: &str| -> usize {
+            *slots.entry(label.to_string()).or_insert_with(|| {
+                labels.push(label.to_string());
+                labels.len() - 1
+            })
+        };
+
+        let ops = program
+            .iter()
+            .map(|command| match command {
+                Command::PutThisPancakeOnTop(adjective) => {
+                    Op::Push(adjective.graphemes(true).count() as u32)
+                }
+                Command::EatThePancakeOnTop => Op::Pop,
+                Command::PutTheTopPancakesTogether => Op::Add,
+                Command::GiveMeAPancake => Op::ReadNumber,
+                Command::HowAboutAHotcake => Op::ReadByte,
+                Command::ShowMeAPancake => Op::Print,
+                Command::TakeFromTheTopPancakes => Op::Sub,
+                Command::FlipThePancakesOnTop => Op::Swap,
+                Command::PutAnotherPancakeOnTop => Op::Dup,
+                Command::Label(label) => Op::Label(slot(label)),
+                Command::IfThePancakeIsntTastyGoOverTo(label) => Op::Jz(slot(label)),
+                Command::IfThePancakeIsTastyGoOverTo(label) => Op::Jnz(slot(label)),
+                Command::PutSyrupOnThePancakes => Op::IncrementAll,
+                Command::PutButterOnThePancakes => Op::Increment,
+                Command::TakeOffTheSyrup => Op::DecrementAll,
+                Command::TakeOffTheButter => Op::Decrement,
+                Command::EatAllOfThePancakes => Op::Halt,
+            })
+            .collect();
+        Bytecode {
+            ops,
+            labels,
+            entries: (0..program.len()).collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    /// Returns the index of the op that is executed first when jumping to the command at each position.
+    #[must_use]
+    pub fn entries(&self) -> &[usize] {
+        &self.entries
+    }
+
+    /// Returns the names of the labels, indexed by their slot.
+    #[must_use]
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Returns the name of the label in the given slot.
+    #[must_use]
+    pub fn label_name(&self, slot: usize) -> Option<&str> {
+        self.labels.get(slot).map(String::as_str)
+    }
+
+    /// Runs the compiled program with the same behavior as [`run_program`](../interpret/fn.run_program.html).
+    ///
+    /// # Errors
+    /// Will return `Err` if the program performs an illegal operation or an io error occurs. See [`Error`](../interpret/enum.Error.html).
+    pub fn run(&self, input: impl Read, mut output: impl Write) -> Result<(), Error> {
+        let mut input = BufReader::new(input);
+        let mut stack: Vec<u32> = Vec::new();
+        let mut positions: Vec<Option<usize>> = vec![None; self.labels.len()];
+        let mut program_counter = 0;
+
+        while let Some(op) = self.ops.get(program_counter) {
+            program_counter += 1;
+            match *op {
+                Op::Push(value) => stack.push(value),
+                Op::Pop => eat(&mut stack)?,
+                Op::Add => put_together(&mut stack)?,
+                Op::ReadNumber => {
+                    let mut line = Vec::new();
+                    input.read_until(b'\n', &mut line)?;
+                    stack.push(parse_pancake(&line)?);
+                }
+                Op::ReadByte => {
+                    let byte = match input.fill_buf()?.first() {
+                        Some(&byte) => {
+                            input.consume(1);
+                            byte
+                        }
+                        None => 0,
+                    };
+                    stack.push(u32::from(byte));
+                }
+                Op::Print => write!(output, "{}", show(&stack)?)?,
+                Op::Sub => take_from(&mut stack)?,
+                Op::Swap => flip(&mut stack)?,
+                Op::Dup => put_another(&mut stack)?,
+                Op::Label(slot) => {
+                    positions[slot] = Some(label_position(&stack, &self.labels[slot])?);
+                }
+                Op::Jz(slot) => {
+                    if top(&stack)? == 0 {
+                        program_counter = self.position(&positions, slot)?;
+                    }
+                }
+                Op::Jnz(slot) => {
+                    if top(&stack)? != 0 {
+                        program_counter = self.position(&positions, slot)?;
+                    }
+                }
+                Op::IncrementAll => put_syrup(&mut stack)?,
+                Op::Increment => put_butter(&mut stack)?,
+                Op::DecrementAll => take_off_syrup(&mut stack)?,
+                Op::Decrement => take_off_butter(&mut stack)?,
+                Op::Halt => break,
+                Op::AddToTop(value) => {
+                    let top = stack.last_mut().ok_or(Error::OutOfPancakes)?;
+                    let result = i64::from(*top).saturating_add(value);
+                    if result < 0 {
+                        return Err(Error::PancakeUnderflow);
+                    }
+                    if result > i64::from(u32::MAX) {
+                        return Err(Error::PancakeOverflow);
+                    }
+                    *top = result as u32;
+                }
+                Op::Drop(count) => {
+                    if stack.len() < count {
+                        return Err(Error::OutOfPancakes);
+                    }
+                    stack.truncate(stack.len() - count);
+                }
+                Op::RequireDepth(depth) => {
+                    if stack.len() < depth {
+                        return Err(Error::OutOfPancakes);
+                    }
+                }
+                Op::Goto(index) => program_counter = index,
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the op to continue with when jumping to the label in the given slot.
+    /// Jumps past the last command end the program.
+    fn position(&self, positions: &[Option<usize>], slot: usize) -> Result<usize, Error> {
+        let position =
+            positions[slot].ok_or_else(|| Error::UndefinedLabel(self.labels[slot].clone()))?;
+        Ok(self.entries.get(position).copied().unwrap_or(usize::MAX))
+    }
+}
@@ -0,0 +1,301 @@
+use crate::analyze;
+use crate::interpret::LabelSemantics;
+use crate::parse::Command;
+use std::fmt::Write;
+use std::ops::Range;
+
+/// A sequence of commands that are always executed after each other.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BasicBlock {
+    commands: Range<usize>,
+}
+
+impl BasicBlock {
+    /// Returns the indices of the commands of this block.
+    #[must_use]
+    pub fn commands(&self) -> Range<usize> {
+        self.commands.clone()
+    }
+}
+
+/// What an edge leads to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Target {
+    /// The basic block with the given index.
+    Block(usize),
+    /// The end of the program.
+    Exit,
+    /// A position that depends on the pancakes at runtime, as the position of the label could not be determined statically.
+    Unknown,
+}
+
+/// When the execution follows an edge.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EdgeKind {
+    /// The execution continues with the next command.
+    Next,
+    /// The top pancake is tasty, a jump by [`IfThePancakeIsTastyGoOverTo`](../parse/enum.Command.html#variant.IfThePancakeIsTastyGoOverTo)
+    /// or not jumping by [`IfThePancakeIsntTastyGoOverTo`](../parse/enum.Command.html#variant.IfThePancakeIsntTastyGoOverTo).
+    Tasty,
+    /// The top pancake is not tasty, a jump by [`IfThePancakeIsntTastyGoOverTo`](../parse/enum.Command.html#variant.IfThePancakeIsntTastyGoOverTo)
+    /// or not jumping by [`IfThePancakeIsTastyGoOverTo`](../parse/enum.Command.html#variant.IfThePancakeIsTastyGoOverTo).
+    NotTasty,
+    /// The program ends by [`EatAllOfThePancakes`](../parse/enum.Command.html#variant.EatAllOfThePancakes).
+    Halt,
+}
+
+/// A possible transfer of control from the end of a basic block.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Edge {
+    from: usize,
+    to: Target,
+    kind: EdgeKind,
+    dynamic: bool,
+}
+
+impl Edge {
+    /// Returns the index of the basic block this edge starts at.
+    #[must_use]
+    pub fn from(&self) -> usize {
+        self.from
+    }
+
+    /// Returns what this edge leads to.
+    #[must_use]
+    pub fn to(&self) -> Target {
+        self.to
+    }
+
+    /// Returns when the execution follows this edge.
+    #[must_use]
+    pub fn kind(&self) -> EdgeKind {
+        self.kind
+    }
+
+    /// Returns whether the edge is a jump to a label whose position could not be determined statically.
+    #[must_use]
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic
+    }
+}
+
+/// The control-flow graph of a program.
+///
+/// With [`LabelSemantics::TopPancake`](../interpret/enum.LabelSemantics.html#variant.TopPancake) a [`Label`](../parse/enum.Command.html#variant.Label)
+/// points to the position given by the top pancake when it is declared.
+/// If these pancakes are built right before every label, the jumps lead to known basic blocks.
+/// Otherwise jumps lead to [`Target::Unknown`](./enum.Target.html#variant.Unknown) and are flagged as dynamic.
+/// ```rust
+/// use pancakestack::cfg::{ControlFlowGraph, EdgeKind, Target};
+/// use pancakestack::LabelSemantics;
+///
+/// let program = pancakestack::parse_program_str("\
+/// Put this old pancake on top!
+/// [CAT]
+/// Eat the pancake on top!
+/// How about a hotcake?
+/// Show me a pancake!
+/// If the pancake is tasty, go over to \"CAT\".
+/// Eat all of the pancakes!");
+/// let graph = ControlFlowGraph::new(&program, LabelSemantics::TopPancake);
+/// assert_eq!(graph.blocks().len(), 3);
+/// assert_eq!(graph.blocks()[1].commands(), 2..6);
+///
+/// let edges = graph.successors(1).collect::<Vec<_>>();
+/// assert_eq!(edges[0].to(), Target::Block(1));
+/// assert_eq!(edges[0].kind(), EdgeKind::Tasty);
+/// assert_eq!(edges[1].to(), Target::Block(2));
+/// assert_eq!(edges[1].kind(), EdgeKind::NotTasty);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ControlFlowGraph {
+    blocks: Vec<BasicBlock>,
+    /// The basic block of every command.
+    block_of: Vec<usize>,
+    edges: Vec<Edge>,
+}
+
+impl ControlFlowGraph {
+    /// Builds the control-flow graph of the given program, whose labels point to positions as given by `label_semantics`.
+    #[must_use]
+    pub fn new(program: &[Command<'_>], label_semantics: LabelSemantics) -> Self {
+        let command_edges = analyze::edges(program, label_semantics);
+
+        let mut leaders = vec![false; program.len() + 1];
+        leaders[0] = true;
+        leaders[program.len()] = true;
+        for (index, (command, edges)) in program.iter().zip(&command_edges).enumerate() {
+            if analyze::jump_label(command).is_some() || !edges.next {
+                leaders[index + 1] = true;
+            }
+            for &target in &edges.targets {
+                if target < program.len() {
+                    leaders[target] = true;
+                }
+            }
+        }
+
+        let mut blocks = Vec::new();
+        let mut block_of = Vec::with_capacity(program.len());
+        let mut start = 0;
+        for (index, &leader) in leaders.iter().enumerate().skip(1) {
+            if leader {
+                block_of.resize(index, blocks.len());
+                blocks.push(BasicBlock {
+                    commands: start..index,
+                });
+                start = index;
+            }
+        }
+
+        let target = |position: usize| match block_of.get(position) {
+            Some(&block) => Target::Block(block),
+            None => Target::Exit,
+        };
+        let mut edges = Vec::new();
+        for (from, block) in blocks.iter().enumerate() {
+            let last = block.commands.end - 1;
+            let command_edges = &command_edges[last];
+            let (taken, not_taken) = match &program[last] {
+                Command::IfThePancakeIsTastyGoOverTo(_) => (EdgeKind::Tasty, EdgeKind::NotTasty),
+                Command::IfThePancakeIsntTastyGoOverTo(_) => (EdgeKind::NotTasty, EdgeKind::Tasty),
+                Command::EatAllOfThePancakes => {
+                    edges.push(Edge {
+                        from,
+                        to: Target::Exit,
+                        kind: EdgeKind::Halt,
+                        dynamic: false,
+                    });
+                    continue;
+                }
+                _ => (EdgeKind::Next, EdgeKind::Next),
+            };
+            for &position in &command_edges.targets {
+                edges.push(Edge {
+                    from,
+                    to: target(position),
+                    kind: taken,
+                    dynamic: false,
+                });
+            }
+            if command_edges.anywhere {
+                edges.push(Edge {
+                    from,
+                    to: Target::Unknown,
+                    kind: taken,
+                    dynamic: true,
+                });
+            }
+            edges.push(Edge {
+                from,
+                to: target(last + 1),
+                kind: not_taken,
+                dynamic: false,
+            });
+        }
+
+        ControlFlowGraph {
+            blocks,
+            block_of,
+            edges,
+        }
+    }
+
+    /// Returns the basic blocks in the order of their commands.
+    #[must_use]
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    /// Returns the edges of all basic blocks, ordered by the block they start at.
+    #[must_use]
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Returns the index of the basic block containing the command at the given index.
+    #[must_use]
+    pub fn block_of(&self, command: usize) -> Option<usize> {
+        self.block_of.get(command).copied()
+    }
+
+    /// Returns the edges starting at the basic block with the given index.
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == block)
+    }
+
+    /// Returns the graph in the Graphviz DOT format, showing the commands of the given program in the basic blocks.
+    ///
+    /// Jumps to unknown positions lead to a node labelled `?` and are dashed.
+    /// ```rust
+    /// use pancakestack::cfg::ControlFlowGraph;
+    /// use pancakestack::LabelSemantics;
+    ///
+    /// let program = pancakestack::parse_program_str("Show me a pancake!\nEat all of the pancakes!");
+    /// let dot = ControlFlowGraph::new(&program, LabelSemantics::TopPancake).to_dot(&program);
+    /// assert_eq!(dot, "\
+    /// digraph program {
+    ///     node [shape=box, fontname=\"monospace\"];
+    ///     block0 [label=\"0: Show me a pancake!\\l1: Eat all of the pancakes!\\l\"];
+    ///     exit [shape=doublecircle, label=\"exit\"];
+    ///     block0 -> exit [label=\"halt\"];
+    /// }
+    /// ");
+    /// ```
+    #[must_use]
+    pub fn to_dot(&self, program: &[Command<'_>]) -> String {
+        let mut dot =
+            String::from("digraph program {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for (index, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for command in block.commands() {
+                let _ = write!(
+                    label,
+                    "{command}: {}\\l",
+                    escape(&program[command].to_string())
+                );
+            }
+            let _ = writeln!(dot, "    block{index} [label=\"{label}\"];");
+        }
+        if self.edges.iter().any(|edge| edge.to == Target::Exit) {
+            dot.push_str("    exit [shape=doublecircle, label=\"exit\"];\n");
+        }
+        if self.edges.iter().any(|edge| edge.to == Target::Unknown) {
+            dot.push_str("    unknown [shape=diamond, label=\"?\"];\n");
+        }
+        for edge in &self.edges {
+            let to = match edge.to {
+                Target::Block(block) => format!("block{block}"),
+                Target::Exit => "exit".to_string(),
+                Target::Unknown => "unknown".to_string(),
+            };
+            let mut attributes = Vec::new();
+            match edge.kind {
+                EdgeKind::Next => {}
+                EdgeKind::Tasty => attributes.push("label=\"tasty\""),
+                EdgeKind::NotTasty => attributes.push("label=\"not tasty\""),
+                EdgeKind::Halt => attributes.push("label=\"halt\""),
+            }
+            if edge.dynamic {
+                attributes.push("style=dashed");
+            }
+            if attributes.is_empty() {
+                let _ = writeln!(dot, "    block{} -> {to};", edge.from);
+            } else {
+                let _ = writeln!(
+                    dot,
+                    "    block{} -> {to} [{}];",
+                    edge.from,
+                    attributes.join(", ")
+                );
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Escapes the given text for a quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,60 @@
+//! Code generators turning Pancake Stack programs into source code of other languages.
+//!
+//! The generated programs read their input from stdin, write their output to stdout and behave like [`run_program`](../interpret/fn.run_program.html).
+//! Errors are reported on stderr and end the program with the exit code the `pancakestack` binary uses for the error.
+//! Labels point to the position given by the top pancake, as with [`LabelSemantics::TopPancake`](../interpret/enum.LabelSemantics.html#variant.TopPancake).
+
+use crate::interpret::Error;
+
+pub mod c;
+pub mod rust;
+pub mod wat;
+
+/// Returns the exit code generated programs end with for the given error, the same one the `pancakestack` binary uses.
+#[doc(hidden)]
+pub fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::OutOfPancakes => 10,
+        Error::InvalidPancake(_) => 11,
+        Error::CanNotShowPancake(_) => 12,
+        Error::UndefinedLabel(_) => 13,
+        Error::PancakeUnderflow => 14,
+        Error::PancakeOverflow => 15,
+        Error::Io(_) => 16,
+        Error::InvalidLabelPosition(_) => 17,
+        Error::StepLimitExceeded(_) => 18,
+        Error::StackLimitExceeded(_) => 19,
+        Error::OutputLimitExceeded(_) => 20,
+        Error::DeadlineExceeded => 21,
+        Error::Cancelled => 22,
+    }
+}
+
+/// Returns the names generated programs use for the exit codes of the errors they can fail with, together with the exit codes.
+pub(crate) fn exit_codes() -> [(&'static str, i32); 8] {
+    [
+        ("OUT_OF_PANCAKES", exit_code(&Error::OutOfPancakes)),
+        (
+            "INVALID_PANCAKE",
+            exit_code(&Error::InvalidPancake(String::new())),
+        ),
+        (
+            "CAN_NOT_SHOW_PANCAKE",
+            exit_code(&Error::CanNotShowPancake(0)),
+        ),
+        (
+            "UNDEFINED_LABEL",
+            exit_code(&Error::UndefinedLabel(String::new())),
+        ),
+        ("PANCAKE_UNDERFLOW", exit_code(&Error::PancakeUnderflow)),
+        ("PANCAKE_OVERFLOW", exit_code(&Error::PancakeOverflow)),
+        (
+            "IO",
+            exit_code(&Error::Io(std::io::ErrorKind::Other.into())),
+        ),
+        (
+            "INVALID_LABEL_POSITION",
+            exit_code(&Error::InvalidLabelPosition(String::new())),
+        ),
+    ]
+}
@@ -0,0 +1,360 @@
+use crate::bytecode::{Bytecode, Op};
+use crate::codegen::exit_codes;
+use crate::parse::Command;
+use std::fmt::Write;
+
+/// The part of the generated program that does not depend on the commands.
+const RUNTIME: &str = r#"static uint32_t *stack = NULL;
+static size_t length = 0;
+static size_t capacity = 0;
+
+/* functions are inline so that the ones a program does not use do not cause warnings */
+static inline void fail(int code, const char *format, ...) {
+    va_list arguments;
+    fflush(stdout);
+    fputs("error: ", stderr);
+    va_start(arguments, format);
+    vfprintf(stderr, format, arguments);
+    va_end(arguments);
+    fputc('\n', stderr);
+    exit(code);
+}
+
+static inline void require(size_t depth) {
+    if (length < depth) {
+        fail(OUT_OF_PANCAKES, "Out of pancakes!");
+    }
+}
+
+static inline void push(uint32_t value) {
+    if (length == capacity) {
+        capacity = capacity == 0 ? 64 : capacity * 2;
+        stack = realloc(stack, capacity * sizeof *stack);
+        if (stack == NULL) {
+            fputs("error: out of memory\n", stderr);
+            exit(EXIT_FAILURE);
+        }
+    }
+    stack[length++] = value;
+}
+
+static inline uint32_t top(void) {
+    require(1);
+    return stack[length - 1];
+}
+
+static inline void pop(void) {
+    require(1);
+    length--;
+}
+
+static inline void add(void) {
+    uint32_t first, second;
+    require(2);
+    first = stack[length - 1];
+    second = stack[length - 2];
+    if (first > UINT32_MAX - second) {
+        fail(PANCAKE_OVERFLOW, "Pancake overflowed its domain.");
+    }
+    length--;
+    stack[length - 1] = first + second;
+}
+
+static inline void sub(void) {
+    uint32_t first, second;
+    require(2);
+    first = stack[length - 1];
+    second = stack[length - 2];
+    if (first < second) {
+        fail(PANCAKE_UNDERFLOW, "Pancake underflowed its domain.");
+    }
+    length--;
+    stack[length - 1] = first - second;
+}
+
+static inline void read_number(void) {
+    char *line = NULL;
+    size_t line_length = 0, line_capacity = 0, end, index;
+    uint32_t number = 0;
+    bool valid;
+    int c;
+
+    fflush(stdout);
+    do {
+        c = getchar();
+        if (c != EOF) {
+            if (line_length + 1 >= line_capacity) {
+                line_capacity = line_capacity == 0 ? 64 : line_capacity * 2;
+                line = realloc(line, line_capacity);
+                if (line == NULL) {
+                    fputs("error: out of memory\n", stderr);
+                    exit(EXIT_FAILURE);
+                }
+            }
+            line[line_length++] = (char)c;
+        }
+    } while (c != EOF && c != '\n');
+    if (ferror(stdin)) {
+        fail(IO, "could not read input");
+    }
+
+    end = line_length;
+    while (end > 0 && (line[end - 1] == '\n' || line[end - 1] == '\r')) {
+        end--;
+    }
+    index = end > 0 && line[0] == '+' ? 1 : 0;
+    valid = index < end;
+    for (; valid && index < end; index++) {
+        uint32_t digit = (uint32_t)(line[index] - '0');
+        if (line[index] < '0' || line[index] > '9' || number > (UINT32_MAX - digit) / 10) {
+            valid = false;
+        } else {
+            number = number * 10 + digit;
+        }
+    }
+    if (!valid) {
+        fail(INVALID_PANCAKE, "Invalid pancake: %.*s", (int)line_length, line == NULL ? "" : line);
+    }
+    free(line);
+    push(number);
+}
+
+static inline void read_byte(void) {
+    int c;
+    fflush(stdout);
+    c = getchar();
+    if (c == EOF && ferror(stdin)) {
+        fail(IO, "could not read input");
+    }
+    push(c == EOF ? 0 : (uint32_t)c);
+}
+
+static inline void print(void) {
+    uint32_t c = top();
+    if (c > 0x10FFFF || (c >= 0xD800 && c <= 0xDFFF)) {
+        fail(CAN_NOT_SHOW_PANCAKE, "Pancake can not be shown (invalid char): %" PRIu32, c);
+    }
+    if (c < 0x80) {
+        putchar((int)c);
+    } else if (c < 0x800) {
+        putchar((int)(0xC0 | (c >> 6)));
+        putchar((int)(0x80 | (c & 0x3F)));
+    } else if (c < 0x10000) {
+        putchar((int)(0xE0 | (c >> 12)));
+        putchar((int)(0x80 | ((c >> 6) & 0x3F)));
+        putchar((int)(0x80 | (c & 0x3F)));
+    } else {
+        putchar((int)(0xF0 | (c >> 18)));
+        putchar((int)(0x80 | ((c >> 12) & 0x3F)));
+        putchar((int)(0x80 | ((c >> 6) & 0x3F)));
+        putchar((int)(0x80 | (c & 0x3F)));
+    }
+    if (ferror(stdout)) {
+        fail(IO, "could not write output");
+    }
+}
+
+static inline void swap(void) {
+    uint32_t first;
+    require(2);
+    first = stack[length - 1];
+    stack[length - 1] = stack[length - 2];
+    stack[length - 2] = first;
+}
+
+static inline void dup(void) {
+    push(top());
+}
+
+static size_t label_positions[LABEL_SLOTS];
+static bool label_defined[LABEL_SLOTS];
+
+static inline void label(size_t slot) {
+    if (top() == 0) {
+        fail(INVALID_LABEL_POSITION, "Label \"%s\" can not be declared on a zero pancake", LABELS[slot]);
+    }
+    label_positions[slot] = (size_t)(top() - 1u);
+    label_defined[slot] = true;
+}
+
+static inline size_t label_position(size_t slot) {
+    if (!label_defined[slot]) {
+        fail(UNDEFINED_LABEL, "Use of undefined label \"%s\"", LABELS[slot]);
+    }
+    return label_positions[slot];
+}
+
+static inline void increment_all(void) {
+    size_t index;
+    for (index = 0; index < length; index++) {
+        if (stack[index] == UINT32_MAX) {
+            fail(PANCAKE_OVERFLOW, "Pancake overflowed its domain.");
+        }
+    }
+    for (index = 0; index < length; index++) {
+        stack[index]++;
+    }
+}
+
+static inline void increment(void) {
+    if (top() == UINT32_MAX) {
+        fail(PANCAKE_OVERFLOW, "Pancake overflowed its domain.");
+    }
+    stack[length - 1]++;
+}
+
+static inline void decrement_all(void) {
+    size_t index;
+    for (index = 0; index < length; index++) {
+        if (stack[index] == 0) {
+            fail(PANCAKE_UNDERFLOW, "Pancake underflowed its domain.");
+        }
+    }
+    for (index = 0; index < length; index++) {
+        stack[index]--;
+    }
+}
+
+static inline void decrement(void) {
+    if (top() == 0) {
+        fail(PANCAKE_UNDERFLOW, "Pancake underflowed its domain.");
+    }
+    stack[length - 1]--;
+}
+"#;
+
+/// Generates a portable C99 program with the same behavior as running the given program with [`run_program`](../../interpret/fn.run_program.html).
+///
+/// Every command gets a C label, jumps to a Pancake Stack label go through a `switch` over the position of the command it points to.
+/// Pancakes are printed encoded as UTF-8.
+/// ```rust
+/// let program = pancakestack::parse_program_str("Put this test pancake on top!\nShow me a pancake!");
+/// let source = pancakestack::codegen::c::generate(&program);
+/// assert!(source.contains("int main(void)"));
+/// ```
+#[must_use]
+pub fn generate(program: &[Command<'_>]) -> String {
+    let bytecode = Bytecode::compile(program);
+    let mut result = String::new();
+    let _ = writeln!(
+        result,
+        "/* Generated by pancakestack {}. */\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    result.push_str(
+        "#include <inttypes.h>
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>\n\n",
+    );
+    for (name, code) in &exit_codes() {
+        let _ = writeln!(result, "#define {name} {code}");
+    }
+    // zero-length arrays are not allowed
+    let _ = writeln!(
+        result,
+        "#define LABEL_SLOTS {}\n",
+        bytecode.labels().len().max(1)
+    );
+    let labels = bytecode
+        .labels()
+        .iter()
+        .map(|label| string_literal(label))
+        .collect::<Vec<_>>();
+    let _ = writeln!(
+        result,
+        "static const char *const LABELS[LABEL_SLOTS] = {{{}}};\n",
+        if labels.is_empty() {
+            "NULL".to_string()
+        } else {
+            labels.join(", ")
+        }
+    );
+    result.push_str(RUNTIME);
+
+    result.push_str("\nint main(void) {\n");
+    // programs without jumps do not need to dispatch positions, unused labels would cause warnings
+    let dispatch = bytecode
+        .ops()
+        .iter()
+        .any(|op| matches!(op, Op::Jz(_) | Op::Jnz(_)));
+    let end = dispatch || bytecode.ops().contains(&Op::Halt);
+    if dispatch {
+        result.push_str(
+            "    size_t position;\n    goto start;\n\ndispatch:\n    switch (position) {\n",
+        );
+        for index in 0..bytecode.ops().len() {
+            let _ = writeln!(result, "    case {index}: goto command_{index};");
+        }
+        result.push_str("    default: goto end;\n    }\n\nstart:\n");
+    }
+    for (index, op) in bytecode.ops().iter().enumerate() {
+        let statement = match *op {
+            Op::Push(value) => format!("push({value}u);"),
+            Op::Pop => "pop();".to_string(),
+            Op::Add => "add();".to_string(),
+            Op::ReadNumber => "read_number();".to_string(),
+            Op::ReadByte => "read_byte();".to_string(),
+            Op::Print => "print();".to_string(),
+            Op::Sub => "sub();".to_string(),
+            Op::Swap => "swap();".to_string(),
+            Op::Dup => "dup();".to_string(),
+            Op::Label(slot) => format!("label({slot});"),
+            Op::Jz(slot) => format!(
+                "if (top() == 0) {{\n        position = label_position({slot});\n        goto dispatch;\n    }}"
+            ),
+            Op::Jnz(slot) => format!(
+                "if (top() != 0) {{\n        position = label_position({slot});\n        goto dispatch;\n    }}"
+            ),
+            Op::IncrementAll => "increment_all();".to_string(),
+            Op::Increment => "increment();".to_string(),
+            Op::DecrementAll => "decrement_all();".to_string(),
+            Op::Decrement => "decrement();".to_string(),
+            Op::Halt => "goto end;".to_string(),
+            Op::AddToTop(_) | Op::Drop(_) | Op::RequireDepth(_) | Op::Goto(_) => {
+                unreachable!("compiled programs do not contain fused ops")
+            }
+        };
+        let comment = program[index].to_string().replace("*/", "* /");
+        if dispatch {
+            let _ = writeln!(result, "command_{index}: /* {comment} */\n    {statement}");
+        } else {
+            let _ = writeln!(result, "    /* {comment} */\n    {statement}");
+        }
+    }
+    if end {
+        result.push_str("\nend:\n");
+    }
+    result.push_str(
+        "    if (fflush(stdout) != 0) {
+        fail(IO, \"could not write output\");
+    }
+    free(stack);
+    return 0;
+}
+",
+    );
+    result
+}
+
+/// Returns a C string literal containing the given text encoded as UTF-8.
+fn string_literal(text: &str) -> String {
+    let mut result = String::from("\"");
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => {
+                result.push('\\');
+                result.push(char::from(byte));
+            }
+            b' '..=b'~' => result.push(char::from(byte)),
+            // octal escapes are always three digits long, so no following character can be mistaken for a part of them
+            _ => {
+                let _ = write!(result, "\\{byte:03o}");
+            }
+        }
+    }
+    result.push('"');
+    result
+}
@@ -0,0 +1,267 @@
+use crate::bytecode::{Bytecode, Op};
+use crate::codegen::exit_codes;
+use crate::parse::Command;
+use std::fmt::Write;
+
+/// The part of the generated program that does not depend on the commands.
+const RUNTIME: &str = r#"use std::io::{self, BufRead, Write};
+use std::process;
+
+struct Pancakes {
+    stack: Vec<u32>,
+    labels: Vec<Option<usize>>,
+    input: io::BufReader<io::Stdin>,
+    output: io::BufWriter<io::Stdout>,
+}
+
+impl Pancakes {
+    fn fail(&mut self, code: i32, message: &str) -> ! {
+        let _ = self.output.flush();
+        eprintln!("error: {}", message);
+        process::exit(code);
+    }
+
+    fn top(&mut self) -> u32 {
+        match self.stack.last() {
+            Some(&top) => top,
+            None => self.fail(OUT_OF_PANCAKES, "Out of pancakes!"),
+        }
+    }
+
+    fn require(&mut self, depth: usize) {
+        if self.stack.len() < depth {
+            self.fail(OUT_OF_PANCAKES, "Out of pancakes!");
+        }
+    }
+
+    fn pop(&mut self) {
+        self.require(1);
+        self.stack.pop();
+    }
+
+    fn add(&mut self) {
+        self.require(2);
+        let first = self.stack[self.stack.len() - 1];
+        let second = self.stack[self.stack.len() - 2];
+        match first.checked_add(second) {
+            Some(result) => self.replace_top_two(result),
+            None => self.fail(PANCAKE_OVERFLOW, "Pancake overflowed its domain."),
+        }
+    }
+
+    fn sub(&mut self) {
+        self.require(2);
+        let first = self.stack[self.stack.len() - 1];
+        let second = self.stack[self.stack.len() - 2];
+        match first.checked_sub(second) {
+            Some(result) => self.replace_top_two(result),
+            None => self.fail(PANCAKE_UNDERFLOW, "Pancake underflowed its domain."),
+        }
+    }
+
+    fn replace_top_two(&mut self, value: u32) {
+        let length = self.stack.len();
+        self.stack.truncate(length - 2);
+        self.stack.push(value);
+    }
+
+    fn flush(&mut self) {
+        if let Err(error) = self.output.flush() {
+            self.fail(IO, &error.to_string());
+        }
+    }
+
+    fn read_number(&mut self) {
+        self.flush();
+        let mut line = Vec::new();
+        if let Err(error) = self.input.read_until(b'\n', &mut line) {
+            self.fail(IO, &error.to_string());
+        }
+        let line = String::from_utf8_lossy(&line).into_owned();
+        match line.trim_end_matches(&['\n', '\r'][..]).parse() {
+            Ok(number) => self.stack.push(number),
+            Err(_) => self.fail(INVALID_PANCAKE, &format!("Invalid pancake: {}", line)),
+        }
+    }
+
+    fn read_byte(&mut self) {
+        self.flush();
+        let byte = match self.input.fill_buf() {
+            Ok(buffer) => buffer.first().copied(),
+            Err(error) => self.fail(IO, &error.to_string()),
+        };
+        if byte.is_some() {
+            self.input.consume(1);
+        }
+        self.stack.push(u32::from(byte.unwrap_or(0)));
+    }
+
+    fn print(&mut self) {
+        let top = self.top();
+        let c = match std::char::from_u32(top) {
+            Some(c) => c,
+            None => self.fail(
+                CAN_NOT_SHOW_PANCAKE,
+                &format!("Pancake can not be shown (invalid char): {}", top),
+            ),
+        };
+        if let Err(error) = write!(self.output, "{}", c) {
+            self.fail(IO, &error.to_string());
+        }
+    }
+
+    fn swap(&mut self) {
+        self.require(2);
+        let length = self.stack.len();
+        self.stack.swap(length - 1, length - 2);
+    }
+
+    fn dup(&mut self) {
+        let top = self.top();
+        self.stack.push(top);
+    }
+
+    fn label(&mut self, slot: usize) {
+        let top = self.top();
+        if top == 0 {
+            self.fail(
+                INVALID_LABEL_POSITION,
+                &format!("Label \"{}\" can not be declared on a zero pancake", LABELS[slot]),
+            );
+        }
+        self.labels[slot] = Some(top as usize - 1);
+    }
+
+    fn position(&mut self, slot: usize) -> usize {
+        match self.labels[slot] {
+            Some(position) => position,
+            None => self.fail(
+                UNDEFINED_LABEL,
+                &format!("Use of undefined label \"{}\"", LABELS[slot]),
+            ),
+        }
+    }
+
+    fn increment_all(&mut self) {
+        if self.stack.contains(&u32::MAX) {
+            self.fail(PANCAKE_OVERFLOW, "Pancake overflowed its domain.");
+        }
+        for value in self.stack.iter_mut() {
+            *value += 1;
+        }
+    }
+
+    fn increment(&mut self) {
+        let top = self.top();
+        match top.checked_add(1) {
+            Some(result) => *self.stack.last_mut().unwrap() = result,
+            None => self.fail(PANCAKE_OVERFLOW, "Pancake overflowed its domain."),
+        }
+    }
+
+    fn decrement_all(&mut self) {
+        if self.stack.contains(&0) {
+            self.fail(PANCAKE_UNDERFLOW, "Pancake underflowed its domain.");
+        }
+        for value in self.stack.iter_mut() {
+            *value -= 1;
+        }
+    }
+
+    fn decrement(&mut self) {
+        let top = self.top();
+        match top.checked_sub(1) {
+            Some(result) => *self.stack.last_mut().unwrap() = result,
+            None => self.fail(PANCAKE_UNDERFLOW, "Pancake underflowed its domain."),
+        }
+    }
+}
+"#;
+
+/// Generates a standalone Rust program with the same behavior as running the given program with [`run_program`](../../interpret/fn.run_program.html).
+///
+/// The generated `main` executes the commands in a `loop`/`match` state machine over the position of the current command,
+/// so that labels can point to any command like they can in the interpreter.
+/// ```rust
+/// let program = pancakestack::parse_program_str("Put this test pancake on top!\nShow me a pancake!");
+/// let source = pancakestack::codegen::rust::generate(&program);
+/// assert!(source.contains("fn main()"));
+/// ```
+#[must_use]
+pub fn generate(program: &[Command<'_>]) -> String {
+    let bytecode = Bytecode::compile(program);
+    let mut result = String::new();
+    let _ = writeln!(
+        result,
+        "// Generated by pancakestack {}.\n#![allow(dead_code)]\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    for (name, code) in &exit_codes() {
+        let _ = writeln!(result, "const {name}: i32 = {code};");
+    }
+    let _ = writeln!(
+        result,
+        "const LABELS: [&str; {}] = {:?};\n",
+        bytecode.labels().len(),
+        bytecode.labels()
+    );
+    result.push_str(RUNTIME);
+
+    result.push_str(
+        "
+fn main() {
+    let mut pancakes = Pancakes {
+        stack: Vec::new(),
+        labels: vec![None; LABELS.len()],
+        input: io::BufReader::new(io::stdin()),
+        output: io::BufWriter::new(io::stdout()),
+    };
+    let mut position: usize = 0;
+    loop {
+        match position {
+",
+    );
+    for (index, op) in bytecode.ops().iter().enumerate() {
+        let statement = match *op {
+            Op::Push(value) => format!("pancakes.stack.push({value});"),
+            Op::Pop => "pancakes.pop();".to_string(),
+            Op::Add => "pancakes.add();".to_string(),
+            Op::ReadNumber => "pancakes.read_number();".to_string(),
+            Op::ReadByte => "pancakes.read_byte();".to_string(),
+            Op::Print => "pancakes.print();".to_string(),
+            Op::Sub => "pancakes.sub();".to_string(),
+            Op::Swap => "pancakes.swap();".to_string(),
+            Op::Dup => "pancakes.dup();".to_string(),
+            Op::Label(slot) => format!("pancakes.label({slot});"),
+            Op::Jz(slot) => format!(
+                "if pancakes.top() == 0 {{\n                    position = pancakes.position({slot});\n                    continue;\n                }}"
+            ),
+            Op::Jnz(slot) => format!(
+                "if pancakes.top() != 0 {{\n                    position = pancakes.position({slot});\n                    continue;\n                }}"
+            ),
+            Op::IncrementAll => "pancakes.increment_all();".to_string(),
+            Op::Increment => "pancakes.increment();".to_string(),
+            Op::DecrementAll => "pancakes.decrement_all();".to_string(),
+            Op::Decrement => "pancakes.decrement();".to_string(),
+            Op::Halt => "break;".to_string(),
+            Op::AddToTop(_) | Op::Drop(_) | Op::RequireDepth(_) | Op::Goto(_) => {
+                unreachable!("compiled programs do not contain fused ops")
+            }
+        };
+        let _ = writeln!(
+            result,
+            "            // {}\n            {index} => {{\n                {statement}\n            }}",
+            program[index].to_string().escape_debug()
+        );
+    }
+    result.push_str(
+        "            _ => break,
+        }
+        position += 1;
+    }
+    pancakes.flush();
+}
+",
+    );
+    result
+}
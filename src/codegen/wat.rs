@@ -0,0 +1,294 @@
+use crate::bytecode::{Bytecode, Op};
+use crate::codegen::{exit_code, exit_codes};
+use crate::interpret::Error;
+use crate::parse::Command;
+use std::fmt::Write;
+
+/// The functions of the generated module that do not depend on the commands.
+///
+/// Functions that can fail return `0` on success and the exit code of the error otherwise.
+const RUNTIME: &str = r#"  (memory (export "memory") 1)
+  (global $length (mut i32) (i32.const 0))
+
+  ;; returns the address of the pancake at the given depth, the top pancake has depth 1
+  (func $address (param $depth i32) (result i32)
+    (i32.shl (i32.sub (global.get $length) (local.get $depth)) (i32.const 2)))
+
+  (func $require (param $depth i32) (result i32)
+    (if (result i32) (i32.lt_u (global.get $length) (local.get $depth))
+      (then (i32.const $OUT_OF_PANCAKES))
+      (else (i32.const 0))))
+
+  (func $top (result i32)
+    (i32.load (call $address (i32.const 1))))
+
+  (func $push (param $value i32)
+    (if (i32.gt_u
+          (i32.shl (i32.add (global.get $length) (i32.const 1)) (i32.const 2))
+          (i32.shl (memory.size) (i32.const 16)))
+      (then
+        (if (i32.eq (memory.grow (i32.const 1)) (i32.const -1))
+          (then unreachable))))
+    (i32.store (i32.shl (global.get $length) (i32.const 2)) (local.get $value))
+    (global.set $length (i32.add (global.get $length) (i32.const 1))))
+
+  (func $pop (result i32)
+    (if (i32.eqz (global.get $length))
+      (then (return (i32.const $OUT_OF_PANCAKES))))
+    (global.set $length (i32.sub (global.get $length) (i32.const 1)))
+    (i32.const 0))
+
+  (func $add (result i32) (local $first i32) (local $second i32)
+    (if (i32.lt_u (global.get $length) (i32.const 2))
+      (then (return (i32.const $OUT_OF_PANCAKES))))
+    (local.set $first (i32.load (call $address (i32.const 1))))
+    (local.set $second (i32.load (call $address (i32.const 2))))
+    (if (i32.lt_u (i32.add (local.get $first) (local.get $second)) (local.get $first))
+      (then (return (i32.const $PANCAKE_OVERFLOW))))
+    (global.set $length (i32.sub (global.get $length) (i32.const 1)))
+    (i32.store (call $address (i32.const 1)) (i32.add (local.get $first) (local.get $second)))
+    (i32.const 0))
+
+  (func $sub (result i32) (local $first i32) (local $second i32)
+    (if (i32.lt_u (global.get $length) (i32.const 2))
+      (then (return (i32.const $OUT_OF_PANCAKES))))
+    (local.set $first (i32.load (call $address (i32.const 1))))
+    (local.set $second (i32.load (call $address (i32.const 2))))
+    (if (i32.lt_u (local.get $first) (local.get $second))
+      (then (return (i32.const $PANCAKE_UNDERFLOW))))
+    (global.set $length (i32.sub (global.get $length) (i32.const 1)))
+    (i32.store (call $address (i32.const 1)) (i32.sub (local.get $first) (local.get $second)))
+    (i32.const 0))
+
+  (func $give_me_a_pancake (result i32) (local $number i64)
+    (local.set $number (call $read_number))
+    (if (i64.eq (local.get $number) (i64.const -1))
+      (then (return (i32.const $INVALID_PANCAKE))))
+    (if (i64.lt_s (local.get $number) (i64.const 0))
+      (then (return (i32.const $IO))))
+    (call $push (i32.wrap_i64 (local.get $number)))
+    (i32.const 0))
+
+  (func $how_about_a_hotcake (result i32) (local $byte i32)
+    (local.set $byte (call $read_byte))
+    (if (i32.lt_s (local.get $byte) (i32.const 0))
+      (then (return (i32.const $IO))))
+    (call $push (local.get $byte))
+    (i32.const 0))
+
+  (func $show_me_a_pancake (result i32) (local $pancake i32)
+    (if (i32.eqz (global.get $length))
+      (then (return (i32.const $OUT_OF_PANCAKES))))
+    (local.set $pancake (call $top))
+    (if (i32.or
+          (i32.gt_u (local.get $pancake) (i32.const 0x10FFFF))
+          (i32.and
+            (i32.ge_u (local.get $pancake) (i32.const 0xD800))
+            (i32.le_u (local.get $pancake) (i32.const 0xDFFF))))
+      (then (return (i32.const $CAN_NOT_SHOW_PANCAKE))))
+    (if (call $write_char (local.get $pancake))
+      (then (return (i32.const $IO))))
+    (i32.const 0))
+
+  (func $swap (result i32) (local $first i32)
+    (if (i32.lt_u (global.get $length) (i32.const 2))
+      (then (return (i32.const $OUT_OF_PANCAKES))))
+    (local.set $first (i32.load (call $address (i32.const 1))))
+    (i32.store (call $address (i32.const 1)) (i32.load (call $address (i32.const 2))))
+    (i32.store (call $address (i32.const 2)) (local.get $first))
+    (i32.const 0))
+
+  (func $dup (result i32)
+    (if (i32.eqz (global.get $length))
+      (then (return (i32.const $OUT_OF_PANCAKES))))
+    (call $push (call $top))
+    (i32.const 0))
+
+  (func $increment_all (result i32) (local $address i32)
+    (local.set $address (i32.const 0))
+    (block $done
+      (loop $check
+        (br_if $done (i32.ge_u (local.get $address) (i32.shl (global.get $length) (i32.const 2))))
+        (if (i32.eq (i32.load (local.get $address)) (i32.const -1))
+          (then (return (i32.const $PANCAKE_OVERFLOW))))
+        (local.set $address (i32.add (local.get $address) (i32.const 4)))
+        (br $check)))
+    (local.set $address (i32.const 0))
+    (block $done
+      (loop $increment
+        (br_if $done (i32.ge_u (local.get $address) (i32.shl (global.get $length) (i32.const 2))))
+        (i32.store (local.get $address) (i32.add (i32.load (local.get $address)) (i32.const 1)))
+        (local.set $address (i32.add (local.get $address) (i32.const 4)))
+        (br $increment)))
+    (i32.const 0))
+
+  (func $increment (result i32)
+    (if (i32.eqz (global.get $length))
+      (then (return (i32.const $OUT_OF_PANCAKES))))
+    (if (i32.eq (call $top) (i32.const -1))
+      (then (return (i32.const $PANCAKE_OVERFLOW))))
+    (i32.store (call $address (i32.const 1)) (i32.add (call $top) (i32.const 1)))
+    (i32.const 0))
+
+  (func $decrement_all (result i32) (local $address i32)
+    (local.set $address (i32.const 0))
+    (block $done
+      (loop $check
+        (br_if $done (i32.ge_u (local.get $address) (i32.shl (global.get $length) (i32.const 2))))
+        (if (i32.eqz (i32.load (local.get $address)))
+          (then (return (i32.const $PANCAKE_UNDERFLOW))))
+        (local.set $address (i32.add (local.get $address) (i32.const 4)))
+        (br $check)))
+    (local.set $address (i32.const 0))
+    (block $done
+      (loop $decrement
+        (br_if $done (i32.ge_u (local.get $address) (i32.shl (global.get $length) (i32.const 2))))
+        (i32.store (local.get $address) (i32.sub (i32.load (local.get $address)) (i32.const 1)))
+        (local.set $address (i32.add (local.get $address) (i32.const 4)))
+        (br $decrement)))
+    (i32.const 0))
+
+  (func $decrement (result i32)
+    (if (i32.eqz (global.get $length))
+      (then (return (i32.const $OUT_OF_PANCAKES))))
+    (if (i32.eqz (call $top))
+      (then (return (i32.const $PANCAKE_UNDERFLOW))))
+    (i32.store (call $address (i32.const 1)) (i32.sub (call $top) (i32.const 1)))
+    (i32.const 0))
+"#;
+
+/// Generates a WebAssembly module in the text format with the same behavior as running the given program with [`run_program`](../../interpret/fn.run_program.html).
+///
+/// The module exports its `memory` and a function `run` taking no parameters, which returns `0` if the program finished
+/// or the exit code the `pancakestack` binary uses for the error that occurred.
+/// It imports the following functions from the `pancakestack` module, which the host has to provide:
+/// - `read_byte: [] -> [i32]` returns the next byte of the input, `0` at the end of the input or a negative value if reading failed.
+/// - `read_number: [] -> [i64]` reads a line of the input and returns the number it contains, `-1` if it is not a valid `u32` or another negative value if reading failed.
+/// - `write_char: [i32] -> [i32]` writes the given Unicode scalar value to the output, returning `0` on success.
+///
+/// ```rust
+/// let program = pancakestack::parse_program_str("Put this test pancake on top!\nShow me a pancake!");
+/// let module = pancakestack::codegen::wat::generate(&program);
+/// assert!(module.contains("(export \"run\")"));
+/// ```
+#[must_use]
+pub fn generate(program: &[Command<'_>]) -> String {
+    let bytecode = Bytecode::compile(program);
+    let mut runtime = RUNTIME.to_string();
+    for (name, code) in &exit_codes() {
+        runtime = runtime.replace(&format!("${name}"), &code.to_string());
+    }
+    let undefined_label = exit_code(&Error::UndefinedLabel(String::new()));
+    let invalid_label_position = exit_code(&Error::InvalidLabelPosition(String::new()));
+
+    let mut result = String::new();
+    let _ = writeln!(
+        result,
+        ";; Generated by pancakestack {}.\n(module",
+        env!("CARGO_PKG_VERSION")
+    );
+    result.push_str(
+        "  (import \"pancakestack\" \"read_byte\" (func $read_byte (result i32)))
+  (import \"pancakestack\" \"read_number\" (func $read_number (result i64)))
+  (import \"pancakestack\" \"write_char\" (func $write_char (param i32) (result i32)))
+
+",
+    );
+    result.push_str(&runtime);
+
+    // positions are kept as i64 so that -1 can mark undefined labels
+    result.push_str(
+        "\n  (func (export \"run\") (result i32) (local $status i32) (local $position i64)",
+    );
+    for (slot, label) in bytecode.labels().iter().enumerate() {
+        let _ = write!(
+            result,
+            "\n    (local $label_{slot} i64) ;; {}",
+            label.escape_debug()
+        );
+    }
+    result.push_str("\n    (global.set $length (i32.const 0))\n");
+    for slot in 0..bytecode.labels().len() {
+        let _ = writeln!(result, "    (local.set $label_{slot} (i64.const -1))");
+    }
+
+    let length = bytecode.ops().len();
+    let _ = writeln!(
+        result,
+        "    block $fail
+    block $end
+    loop $dispatch
+    (br_if $end (i64.ge_u (local.get $position) (i64.const {length})))"
+    );
+    for index in (0..length).rev() {
+        let _ = writeln!(result, "    block $command_{index}");
+    }
+    if length > 0 {
+        result.push_str("    (br_table");
+        for index in 0..length {
+            let _ = write!(result, " $command_{index}");
+        }
+        result.push_str(" $end (i32.wrap_i64 (local.get $position)))\n");
+    }
+
+    for (index, op) in bytecode.ops().iter().enumerate() {
+        let _ = writeln!(
+            result,
+            "    end ;; {}",
+            program[index].to_string().escape_debug()
+        );
+        let call = |function: &str| format!("(br_if $fail (local.tee $status (call ${function})))");
+        let jump = |condition: &str, slot: usize| {
+            format!(
+                "(br_if $fail (local.tee $status (call $require (i32.const 1))))
+    (if ({condition} (call $top))
+      (then
+        (if (i64.lt_s (local.get $label_{slot}) (i64.const 0))
+          (then
+            (local.set $status (i32.const {undefined_label}))
+            (br $fail)))
+        (local.set $position (local.get $label_{slot}))
+        (br $dispatch)))"
+            )
+        };
+        let statement = match *op {
+            Op::Push(value) => format!("(call $push (i32.const {value}))"),
+            Op::Pop => call("pop"),
+            Op::Add => call("add"),
+            Op::ReadNumber => call("give_me_a_pancake"),
+            Op::ReadByte => call("how_about_a_hotcake"),
+            Op::Print => call("show_me_a_pancake"),
+            Op::Sub => call("sub"),
+            Op::Swap => call("swap"),
+            Op::Dup => call("dup"),
+            Op::Label(slot) => format!(
+                "(br_if $fail (local.tee $status (call $require (i32.const 1))))
+    (if (i32.eqz (call $top))
+      (then
+        (local.set $status (i32.const {invalid_label_position}))
+        (br $fail)))
+    (local.set $label_{slot} (i64.extend_i32_u (i32.sub (call $top) (i32.const 1))))"
+            ),
+            Op::Jz(slot) => jump("i32.eqz", slot),
+            Op::Jnz(slot) => jump("i32.ne (i32.const 0)", slot),
+            Op::IncrementAll => call("increment_all"),
+            Op::Increment => call("increment"),
+            Op::DecrementAll => call("decrement_all"),
+            Op::Decrement => call("decrement"),
+            Op::Halt => "(br $end)".to_string(),
+            Op::AddToTop(_) | Op::Drop(_) | Op::RequireDepth(_) | Op::Goto(_) => {
+                unreachable!("compiled programs do not contain fused ops")
+            }
+        };
+        let _ = writeln!(result, "    {statement}");
+    }
+    result.push_str(
+        "    end ;; $dispatch
+    end ;; $end
+    (return (i32.const 0))
+    end ;; $fail
+    (local.get $status)))
+",
+    );
+    result
+}
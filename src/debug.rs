@@ -0,0 +1,475 @@
+use crate::interpret::{Error, Machine, Status};
+use crate::parse::{parse_program_str_spanned, Command};
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::io::{self, prelude::*, Read, Write};
+
+/// A debugger running a pancakestack program with breakpoints and watchpoints.
+/// ```rust
+/// use pancakestack::debug::{Breakpoint, Debugger, Pause};
+///
+/// let program = "Put this old pancake on top!\n[CAT]\nEat the pancake on top!\nHow about a hotcake?\nShow me a pancake!\nIf the pancake is tasty, go over to \"CAT\".";
+/// let mut debugger = Debugger::from_source(program);
+/// debugger.add_breakpoint(Breakpoint::Line(5));
+///
+/// let mut output = Vec::new();
+/// let pause = debugger.continue_execution(&b"ab"[..], &mut output).unwrap();
+/// assert_eq!(pause, Pause::Breakpoint(Breakpoint::Line(5)));
+/// assert_eq!(debugger.machine().stack(), &[u32::from(b'a')]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Debugger<'a> {
+    program: Vec<Command<'a>>,
+    lines: Vec<usize>,
+    machine: Machine,
+    line_breakpoints: HashSet<usize>,
+    label_breakpoints: HashSet<String>,
+    watchpoints: HashSet<Watchpoint>,
+    /// Whether the debugger paused at a breakpoint and has not executed anything since.
+    at_breakpoint: bool,
+}
+
+/// A location the debugger pauses at before executing it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    /// Pauses before executing the command on the given 1-indexed source line.
+    Line(usize),
+    /// Pauses before executing a `[label]` command with the given name and after jumping to the label.
+    Label(String),
+}
+
+/// A property of the stack the debugger pauses on when it changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Watchpoint {
+    /// Pauses when the number of pancakes changes.
+    StackDepth,
+    /// Pauses when the value of the top pancake changes.
+    TopValue,
+}
+
+/// The reason the debugger paused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pause {
+    /// A single step was completed.
+    Stepped,
+    /// A breakpoint was hit.
+    Breakpoint(Breakpoint),
+    /// The stack depth changed while [`Watchpoint::StackDepth`](./enum.Watchpoint.html#variant.StackDepth) was set.
+    StackDepthChanged { old: usize, new: usize },
+    /// The top pancake changed while [`Watchpoint::TopValue`](./enum.Watchpoint.html#variant.TopValue) was set.
+    TopValueChanged { old: Option<u32>, new: Option<u32> },
+    /// The program executed [`Command::EatAllOfThePancakes`](../parse/enum.Command.html#variant.EatAllOfThePancakes).
+    Halted,
+    /// The program ran past its last command.
+    Finished,
+}
+
+impl<'a> Debugger<'a> {
+    /// Creates a debugger for the given program. The source line of each command is assumed to be its 1-indexed position.
+    #[must_use]
+    pub fn new(program: Vec<Command<'a>>) -> Self {
+        let lines = (1..=program.len()).collect();
+        Self::with_lines(program, lines)
+    }
+
+    /// Parses the given str and creates a debugger for it, remembering the source line of each command.
+    /// Lines that cannot be parsed are skipped like in [`parse_program_str`](../parse/fn.parse_program_str.html).
+    #[must_use]
+    pub fn from_source(source: &'a str) -> Self {
+        let (lines, program) = parse_program_str_spanned(source)
+            .into_iter()
+            .map(|command| (command.line(), command.into_node()))
+            .unzip();
+        Self::with_lines(program, lines)
+    }
+
+    fn with_lines(program: Vec<Command<'a>>, lines: Vec<usize>) -> Self {
+        Debugger {
+            program,
+            lines,
+            machine: Machine::new(),
+            line_breakpoints: HashSet::new(),
+            label_breakpoints: HashSet::new(),
+            watchpoints: HashSet::new(),
+            at_breakpoint: false,
+        }
+    }
+
+    /// Returns the debugged program.
+    #[must_use]
+    pub fn program(&self) -> &[Command<'a>] {
+        &self.program
+    }
+
+    /// Returns the machine executing the program.
+    #[must_use]
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// Returns the source line of the next command to be executed, if there is one.
+    #[must_use]
+    pub fn current_line(&self) -> Option<usize> {
+        self.lines.get(self.machine.program_counter()).copied()
+    }
+
+    /// Returns the next command to be executed, if there is one.
+    #[must_use]
+    pub fn current_command(&self) -> Option<&Command<'a>> {
+        self.program.get(self.machine.program_counter())
+    }
+
+    /// Adds a breakpoint. Returns whether it was not set before.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        match breakpoint {
+            Breakpoint::Line(line) => self.line_breakpoints.insert(line),
+            Breakpoint::Label(label) => self.label_breakpoints.insert(label),
+        }
+    }
+
+    /// Removes a breakpoint. Returns whether it was set.
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        match breakpoint {
+            Breakpoint::Line(line) => self.line_breakpoints.remove(line),
+            Breakpoint::Label(label) => self.label_breakpoints.remove(label),
+        }
+    }
+
+    /// Adds a watchpoint. Returns whether it was not set before.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        self.watchpoints.insert(watchpoint)
+    }
+
+    /// Removes a watchpoint. Returns whether it was set.
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        self.watchpoints.remove(&watchpoint)
+    }
+
+    /// Executes the next command.
+    ///
+    /// # Errors
+    /// Will return `Err` if the command performs an illegal operation or an io error occurs. See [`Error`](../interpret/enum.Error.html).
+    /// The state of the machine is kept so that it can be inspected.
+    pub fn step(&mut self, mut input: impl Read, mut output: impl Write) -> Result<Pause, Error> {
+        let pause = self
+            .execute_next(&mut input, &mut output)?
+            .unwrap_or(Pause::Stepped);
+        Ok(self.paused(pause))
+    }
+
+    /// Executes commands until the command following the current one is reached, stepping over loops that jump back.
+    ///
+    /// # Errors
+    /// Will return `Err` if a command performs an illegal operation or an io error occurs. See [`Error`](../interpret/enum.Error.html).
+    pub fn step_over(
+        &mut self,
+        mut input: impl Read,
+        mut output: impl Write,
+    ) -> Result<Pause, Error> {
+        let target = self.machine.program_counter() + 1;
+        loop {
+            if let Some(pause) = self.execute_next(&mut input, &mut output)? {
+                return Ok(self.paused(pause));
+            }
+            if self.machine.program_counter() == target {
+                return Ok(Pause::Stepped);
+            }
+            if let Some(breakpoint) = self.breakpoint_at_current() {
+                return Ok(self.paused(Pause::Breakpoint(breakpoint)));
+            }
+        }
+    }
+
+    /// Executes commands until a breakpoint or watchpoint is hit or the program ends.
+    /// A breakpoint at the current command is hit before executing it, unless the debugger is already paused at it.
+    ///
+    /// # Errors
+    /// Will return `Err` if a command performs an illegal operation or an io error occurs. See [`Error`](../interpret/enum.Error.html).
+    pub fn continue_execution(
+        &mut self,
+        mut input: impl Read,
+        mut output: impl Write,
+    ) -> Result<Pause, Error> {
+        let mut resuming = self.at_breakpoint;
+        loop {
+            if !resuming {
+                if let Some(breakpoint) = self.breakpoint_at_current() {
+                    return Ok(self.paused(Pause::Breakpoint(breakpoint)));
+                }
+            }
+            resuming = false;
+            if let Some(pause) = self.execute_next(&mut input, &mut output)? {
+                return Ok(self.paused(pause));
+            }
+        }
+    }
+
+    /// Remembers whether the debugger pauses at a breakpoint, so that continuing does not hit it again.
+    fn paused(&mut self, pause: Pause) -> Pause {
+        self.at_breakpoint = matches!(pause, Pause::Breakpoint(_));
+        pause
+    }
+
+    fn execute_next(
+        &mut self,
+        input: &mut impl Read,
+        output: &mut impl Write,
+    ) -> Result<Option<Pause>, Error> {
+        if self.machine.is_halted() {
+            return Ok(Some(Pause::Halted));
+        }
+        let command = match self.program.get(self.machine.program_counter()) {
+            Some(command) => command,
+            None => return Ok(Some(Pause::Finished)),
+        };
+
+        self.at_breakpoint = false;
+        let old_depth = self.machine.stack().len();
+        let old_top = self.machine.stack().last().copied();
+        let status = self.machine.execute(command, input, output)?;
+        let new_depth = self.machine.stack().len();
+        let new_top = self.machine.stack().last().copied();
+
+        if status == Status::Halted {
+            return Ok(Some(Pause::Halted));
+        }
+        if self.watchpoints.contains(&Watchpoint::StackDepth) && old_depth != new_depth {
+            return Ok(Some(Pause::StackDepthChanged {
+                old: old_depth,
+                new: new_depth,
+            }));
+        }
+        if self.watchpoints.contains(&Watchpoint::TopValue) && old_top != new_top {
+            return Ok(Some(Pause::TopValueChanged {
+                old: old_top,
+                new: new_top,
+            }));
+        }
+        if let Status::Jumped(_) = status {
+            match command {
+                Command::IfThePancakeIsntTastyGoOverTo(label)
+                | Command::IfThePancakeIsTastyGoOverTo(label)
+                    if self.label_breakpoints.contains(label.as_ref()) =>
+                {
+                    return Ok(Some(Pause::Breakpoint(Breakpoint::Label(
+                        label.to_string(),
+                    ))));
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    fn breakpoint_at_current(&self) -> Option<Breakpoint> {
+        let line = self.current_line()?;
+        if self.line_breakpoints.contains(&line) {
+            return Some(Breakpoint::Line(line));
+        }
+        match self.current_command()? {
+            Command::Label(label) if self.label_breakpoints.contains(label.as_ref()) => {
+                Some(Breakpoint::Label(label.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Writes the pancake stack to the given output, top pancake first.
+    ///
+    /// # Errors
+    /// Will return `Err` if writing to the output fails.
+    pub fn print_stack(&self, mut output: impl Write) -> io::Result<()> {
+        let stack = self.machine.stack();
+        if stack.is_empty() {
+            return writeln!(output, "(no pancakes)");
+        }
+        for (index, pancake) in stack.iter().enumerate().rev() {
+            match char::from_u32(*pancake).filter(|c| !c.is_control()) {
+                Some(c) => writeln!(output, "{index:>4}: {pancake} '{c}'")?,
+                None => writeln!(output, "{index:>4}: {pancake}")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the defined labels and the source lines they jump to to the given output.
+    ///
+    /// # Errors
+    /// Will return `Err` if writing to the output fails.
+    pub fn print_labels(&self, mut output: impl Write) -> io::Result<()> {
+        let mut labels = self.machine.labels().iter().collect::<Vec<_>>();
+        labels.sort();
+        if labels.is_empty() {
+            return writeln!(output, "(no labels)");
+        }
+        for (label, position) in labels {
+            match self.lines.get(*position) {
+                Some(line) => writeln!(output, "[{label}] -> line {line}")?,
+                None => writeln!(output, "[{label}] -> end of program")?,
+            }
+        }
+        Ok(())
+    }
+
+    fn print_location(&self, mut output: impl Write) -> io::Result<()> {
+        match (self.current_line(), self.current_command()) {
+            (Some(line), Some(command)) => writeln!(output, "{line:>4} | {command}"),
+            _ => writeln!(output, "end of program"),
+        }
+    }
+
+    /// Runs an interactive debugging session reading debugger commands from `commands` and writing responses to `console`.
+    /// The debugged program reads from `input` and writes to `output`.
+    ///
+    /// Supported commands are `break <line|label>`, `delete <line|label>`, `watch <depth|top>`, `unwatch <depth|top>`,
+    /// `step`, `next`, `continue`, `stack`, `labels`, `where`, `help` and `quit`.
+    ///
+    /// # Errors
+    /// Will return `Err` if reading commands or writing to the console fails.
+    /// Errors of the debugged program are reported on the console.
+    pub fn run_interactive(
+        &mut self,
+        mut commands: impl BufRead,
+        mut console: impl Write,
+        mut input: impl Read,
+        mut output: impl Write,
+    ) -> io::Result<()> {
+        let mut line = String::new();
+        self.print_location(&mut console)?;
+        loop {
+            write!(console, "(pancake) ")?;
+            console.flush()?;
+            line.clear();
+            if commands.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let mut words = line.split_whitespace();
+            let command = match words.next() {
+                Some(command) => command,
+                None => continue,
+            };
+            let argument = words.next();
+
+            let result = match command {
+                "b" | "break" | "d" | "delete" => {
+                    let breakpoint = match argument {
+                        Some(argument) => match argument.parse() {
+                            Ok(line) => Breakpoint::Line(line),
+                            Err(_) => Breakpoint::Label(argument.to_string()),
+                        },
+                        None => {
+                            writeln!(console, "usage: {command} <line|label>")?;
+                            continue;
+                        }
+                    };
+                    if command.starts_with('b') {
+                        self.add_breakpoint(breakpoint.clone());
+                        writeln!(console, "added {breakpoint}")?;
+                    } else if self.remove_breakpoint(&breakpoint) {
+                        writeln!(console, "deleted {breakpoint}")?;
+                    } else {
+                        writeln!(console, "no {breakpoint}")?;
+                    }
+                    continue;
+                }
+                "w" | "watch" | "unwatch" => {
+                    let watchpoint = match argument {
+                        Some("depth") => Watchpoint::StackDepth,
+                        Some("top") => Watchpoint::TopValue,
+                        _ => {
+                            writeln!(console, "usage: {command} <depth|top>")?;
+                            continue;
+                        }
+                    };
+                    if command == "unwatch" {
+                        self.remove_watchpoint(watchpoint);
+                    } else {
+                        self.add_watchpoint(watchpoint);
+                    }
+                    continue;
+                }
+                "s" | "step" => self.step(&mut input, &mut output),
+                "n" | "next" => self.step_over(&mut input, &mut output),
+                "c" | "continue" => self.continue_execution(&mut input, &mut output),
+                "p" | "stack" => {
+                    self.print_stack(&mut console)?;
+                    continue;
+                }
+                "labels" => {
+                    self.print_labels(&mut console)?;
+                    continue;
+                }
+                "where" => {
+                    self.print_location(&mut console)?;
+                    continue;
+                }
+                "q" | "quit" => return Ok(()),
+                "h" | "help" => {
+                    writeln!(console, "break <line|label>   pause before a line or label")?;
+                    writeln!(console, "delete <line|label>  remove a breakpoint")?;
+                    writeln!(
+                        console,
+                        "watch <depth|top>    pause when the stack depth or top pancake changes"
+                    )?;
+                    writeln!(console, "unwatch <depth|top>  remove a watchpoint")?;
+                    writeln!(console, "step                 execute the next command")?;
+                    writeln!(
+                        console,
+                        "next                 execute until the following command is reached"
+                    )?;
+                    writeln!(
+                        console,
+                        "continue             execute until a breakpoint or watchpoint is hit"
+                    )?;
+                    writeln!(console, "stack                print the pancake stack")?;
+                    writeln!(console, "labels               print the defined labels")?;
+                    writeln!(console, "where                print the next command")?;
+                    writeln!(console, "quit                 end the session")?;
+                    continue;
+                }
+                _ => {
+                    writeln!(console, "unknown command \"{command}\", try \"help\"")?;
+                    continue;
+                }
+            };
+            output.flush()?;
+
+            match result {
+                Ok(Pause::Stepped) => {}
+                Ok(pause) => writeln!(console, "{pause}")?,
+                Err(error) => writeln!(console, "error: {error}")?,
+            }
+            self.print_location(&mut console)?;
+        }
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Line(line) => write!(f, "breakpoint at line {line}"),
+            Breakpoint::Label(label) => write!(f, "breakpoint at label [{label}]"),
+        }
+    }
+}
+
+impl Display for Pause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pause::Stepped => write!(f, "stepped"),
+            Pause::Breakpoint(breakpoint) => write!(f, "hit {breakpoint}"),
+            Pause::StackDepthChanged { old, new } => {
+                write!(f, "stack depth changed from {old} to {new}")
+            }
+            Pause::TopValueChanged { old, new } => {
+                let show = |value: &Option<u32>| match value {
+                    Some(value) => value.to_string(),
+                    None => "nothing".to_string(),
+                };
+                write!(f, "top pancake changed from {} to {}", show(old), show(new))
+            }
+            Pause::Halted => write!(f, "all pancakes were eaten"),
+            Pause::Finished => write!(f, "reached the end of the program"),
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use crate::interpret::{Error, RuntimeError};
+use crate::parse::{Command, ParseDiagnostic, Spanned};
+use crate::suggest::{edit_distance, max_distance};
+use std::fmt::Write;
+use std::ops::Range;
+
+/// A compiler-style report of a problem in a program, pointing at the part of the source it originates from.
+/// ```rust
+/// use pancakestack::diagnostic::Diagnostic;
+///
+/// let source = "Put this good pancake on top!\nShow me the pancake!\n";
+/// let diagnostics = pancakestack::parse_program_str_strict(source).unwrap_err();
+/// let rendered = Diagnostic::from_parse_diagnostic(&diagnostics[0]).render("good.pancake", source);
+/// assert_eq!(rendered, "\
+/// error[E0001]: unknown command
+///  --> good.pancake:2:1
+///   |
+/// 2 | Show me the pancake!
+///   | ^^^^^^^^^^^^^^^^^^^^ not a Pancake Stack command
+///   |
+///   = help: did you mean \"Show me a pancake!\"?
+/// ");
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    code: &'static str,
+    message: String,
+    line: usize,
+    span: Range<usize>,
+    label: String,
+    notes: Vec<String>,
+    help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic for the given byte range of the source, which lies on the given 1-indexed line.
+    #[must_use]
+    pub fn new(
+        code: &'static str,
+        message: impl Into<String>,
+        line: usize,
+        span: Range<usize>,
+    ) -> Self {
+        Diagnostic {
+            code,
+            message: message.into(),
+            line,
+            span,
+            label: String::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    /// Sets the text displayed next to the carets.
+    #[must_use]
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    /// Adds a note displayed below the source.
+    #[must_use]
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Sets a help message displayed below the source, like a suggested fix.
+    #[must_use]
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Creates a diagnostic for a line that could not be parsed, suggesting the command that was most likely meant.
+    #[must_use]
+    pub fn from_parse_diagnostic(diagnostic: &ParseDiagnostic<'_>) -> Self {
+        let error = diagnostic.error();
+        let result = Diagnostic::new(
+            error.code(),
+            "unknown command",
+            diagnostic.line_number(),
+            diagnostic.span(),
+        )
+        .with_label("not a Pancake Stack command");
+        match error.suggestion() {
+            Some(suggestion) => result.with_help(format!("did you mean \"{suggestion}\"?")),
+            None => result,
+        }
+    }
+
+    /// Creates a diagnostic for an error that occurred while running the given source.
+    #[must_use]
+    pub fn from_runtime_error(error: &Spanned<RuntimeError>, source: &str) -> Self {
+        let runtime_error = error.node();
+        let mut span = error.span();
+        let (message, label) = match runtime_error.error() {
+            Error::OutOfPancakes => {
+                let available = runtime_error.stack().len();
+                let label = match required_pancakes(runtime_error.command()) {
+                    1 => "this needs a pancake but there are none".to_string(),
+                    required => format!(
+                        "this needs {required} pancakes but there {}",
+                        match available {
+                            0 => "are none".to_string(),
+                            1 => "is only 1".to_string(),
+                            available => format!("are only {available}"),
+                        }
+                    ),
+                };
+                ("out of pancakes".to_string(), label)
+            }
+            Error::InvalidPancake(input) => (
+                "invalid pancake".to_string(),
+                format!("the input {input:?} is not a number"),
+            ),
+            Error::CanNotShowPancake(pancake) => (
+                "pancake can not be shown".to_string(),
+                format!("{pancake} is not a valid char"),
+            ),
+            Error::UndefinedLabel(label) => {
+                if let Some(label_span) = quoted_span(source, &span, label) {
+                    span = label_span;
+                }
+                (
+                    format!("use of undefined label \"{label}\""),
+                    "this label has not been defined yet".to_string(),
+                )
+            }
+            Error::InvalidLabelPosition(label) => (
+                format!("label \"{label}\" declared on a zero pancake"),
+                "this label would point before the first command".to_string(),
+            ),
+            Error::StepLimitExceeded(limit) => (
+                "step limit exceeded".to_string(),
+                format!("this would execute more than {limit} commands"),
+            ),
+            Error::StackLimitExceeded(limit) => (
+                "stack limit exceeded".to_string(),
+                format!("this would stack more than {limit} pancakes"),
+            ),
+            Error::OutputLimitExceeded(limit) => (
+                "output limit exceeded".to_string(),
+                format!("this would write more than {limit} bytes"),
+            ),
+            Error::DeadlineExceeded => (
+                "deadline exceeded".to_string(),
+                "the deadline passed before this could run".to_string(),
+            ),
+            Error::Cancelled => (
+                "cancelled".to_string(),
+                "the program was cancelled before this could run".to_string(),
+            ),
+            Error::PancakeUnderflow => (
+                "pancake underflow".to_string(),
+                "this would make a pancake smaller than 0".to_string(),
+            ),
+            Error::PancakeOverflow => (
+                "pancake overflow".to_string(),
+                format!("this would make a pancake larger than {}", u32::MAX),
+            ),
+            Error::Io(error) => ("io error".to_string(), error.to_string()),
+        };
+
+        let mut result = Diagnostic::new(runtime_error.error().code(), message, error.line(), span)
+            .with_label(label)
+            .with_note(format!(
+                "the stack was {:?} (top pancake last)",
+                runtime_error.stack()
+            ));
+        if let Error::UndefinedLabel(label) = runtime_error.error() {
+            let defined_labels = source
+                .lines()
+                .filter_map(|line| match Command::from_line(line) {
+                    Ok(Command::Label(label)) => Some(label.into_owned()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            if defined_labels.contains(label) {
+                result = result.with_note(format!(
+                    "label \"{label}\" is declared, but its declaration has not been reached yet"
+                ));
+            } else if let Some(suggestion) = closest(label, defined_labels.into_iter()) {
+                result = result.with_help(format!("did you mean \"{suggestion}\"?"));
+            }
+        }
+        result
+    }
+
+    /// Returns the diagnostic code identifying the kind of problem.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Returns the short description of the problem shown in the header.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the 1-indexed line the problem is located on.
+    #[must_use]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the byte range of the problem in the source.
+    #[must_use]
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Returns the suggestion how to fix the problem, if there is one.
+    #[must_use]
+    pub fn help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    /// Renders the diagnostic for the given source, which is referred to by the given file name.
+    #[must_use]
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let start = self.span.start.min(source.len());
+        let end = self.span.end.clamp(start, source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |index| start + index);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+
+        let column = source[line_start..start].chars().count();
+        let carets = source[start..end.min(line_end)].chars().count().max(1);
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        let mut result = String::new();
+        let _ = writeln!(result, "error[{}]: {}", self.code, self.message);
+        let _ = writeln!(
+            result,
+            "{gutter}--> {file_name}:{}:{}",
+            self.line,
+            column + 1
+        );
+        let _ = writeln!(result, "{gutter} |");
+        let _ = writeln!(result, "{line_number} | {line}");
+        let _ = write!(
+            result,
+            "{gutter} | {}{}",
+            " ".repeat(column),
+            "^".repeat(carets)
+        );
+        if !self.label.is_empty() {
+            let _ = write!(result, " {}", self.label);
+        }
+        result.push('\n');
+        if !self.notes.is_empty() || self.help.is_some() {
+            let _ = writeln!(result, "{gutter} |");
+        }
+        for note in &self.notes {
+            let _ = writeln!(result, "{gutter} = note: {note}");
+        }
+        if let Some(help) = &self.help {
+            let _ = writeln!(result, "{gutter} = help: {help}");
+        }
+        result
+    }
+}
+
+/// Returns the number of pancakes the given command needs on the stack.
+fn required_pancakes(command: &Command<'_>) -> usize {
+    match command {
+        Command::PutTheTopPancakesTogether
+        | Command::TakeFromTheTopPancakes
+        | Command::FlipThePancakesOnTop => 2,
+        _ => 1,
+    }
+}
+
+/// Returns the span of the given label in quotes inside the given span of the source.
+fn quoted_span(source: &str, span: &Range<usize>, label: &str) -> Option<Range<usize>> {
+    let line = source.get(span.clone())?;
+    let index = line.rfind(&format!("\"{label}\""))?;
+    let start = span.start + index + 1;
+    Some(start..start + label.len())
+}
+
+/// Returns the candidate closest to the given text if it is close enough to be a likely typo.
+/// Uses the same threshold as the [command suggestions](../suggest/fn.suggest.html).
+fn closest(text: &str, candidates: impl Iterator<Item = String>) -> Option<String> {
+    let max_distance = max_distance(text.chars().count());
+    candidates
+        .map(|candidate| (edit_distance(text, &candidate), candidate))
+        .filter(|(distance, _)| (1..=max_distance).contains(distance))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
@@ -0,0 +1,52 @@
+use crate::parse::Command;
+use crate::synthesize::Synthesizer;
+
+/// Generates a program printing the given text.
+///
+/// Every char is either built from scratch with a [`Synthesizer`](../synthesize/struct.Synthesizer.html),
+/// or derived from the char printed before it, whichever takes fewer commands.
+/// ```rust
+/// let program = pancakestack::generate::generate_print_program("Hello 🥞!");
+/// let mut output = Vec::new();
+/// pancakestack::run_program(&program, std::io::empty(), &mut output).unwrap();
+/// assert_eq!(output, "Hello 🥞!".as_bytes());
+/// ```
+#[must_use]
+pub fn generate_print_program(text: &str) -> Vec<Command<'static>> {
+    let mut synthesizer = Synthesizer::new();
+
+    let mut program = Vec::new();
+    let mut previous: Option<u32> = None;
+    for c in text.chars() {
+        let value = u32::from(c);
+        match previous {
+            Some(previous) if previous == value => {}
+            Some(previous) if value == previous + 1 => {
+                program.push(Command::PutButterOnThePancakes);
+            }
+            Some(previous) if previous == value + 1 => {
+                program.push(Command::TakeOffTheButter);
+            }
+            Some(previous)
+                if previous < value
+                    && synthesizer.cost(value - previous) + 1 < synthesizer.cost(value) =>
+            {
+                program.extend(synthesizer.synthesize(value - previous));
+                program.push(Command::PutTheTopPancakesTogether);
+            }
+            Some(previous)
+                if previous > value
+                    && synthesizer.cost(previous - value) + 2 < synthesizer.cost(value) =>
+            {
+                program.extend(synthesizer.synthesize(previous - value));
+                program.push(Command::FlipThePancakesOnTop);
+                program.push(Command::TakeFromTheTopPancakes);
+            }
+            // the previous char stays below, it is never used again
+            _ => program.extend(synthesizer.synthesize(value)),
+        }
+        program.push(Command::ShowMeAPancake);
+        previous = Some(value);
+    }
+    program
+}
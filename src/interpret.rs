@@ -1,7 +1,11 @@
-use crate::parse::{parse_program_str, Command};
-use std::collections::HashMap;
+use crate::parse::{parse_program_str, Command, Spanned};
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
 use std::fmt::{self, Display};
 use std::io::{self, prelude::*, BufReader, Read, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 use unicode_segmentation::UnicodeSegmentation;
 
 /// Parses and run the commands read from the given Read using the provided input and output.
@@ -26,25 +30,15 @@ pub fn run_program_from_read(
     mut output: impl Write,
 ) -> Result<(), Error> {
     let mut program = BufReader::new(program);
-    let mut input = BufReader::new(input);
+    let mut input = input;
 
-    let mut stack = Vec::new();
-    let mut labels = HashMap::new();
+    let mut machine = Machine::new();
     let mut executed = Vec::new();
-    let mut current_statement: Option<usize> = None;
 
     let mut program_line = String::new();
-    let mut in_line = String::new();
-    loop {
-        let command = if let Some(ref mut index) = current_statement {
-            if let Some(c) = executed.get(*index) {
-                *index += 1;
-                c
-            } else {
-                current_statement = None;
-                continue;
-            }
-        } else {
+    while !machine.is_halted() {
+        // jumps to commands that have not been read yet continue with the next unread command
+        while machine.program_counter() >= executed.len() {
             program_line.clear();
             let length = program.read_line(&mut program_line)?;
             if length == 0 {
@@ -52,154 +46,32 @@ pub fn run_program_from_read(
             }
             trim_newline(&mut program_line);
 
-            fn trim_newline(s: &mut String) {
-                if s.ends_with('\n') {
-                    s.pop();
-                    if s.ends_with('\r') {
-                        s.pop();
+            match Command::from_line(&program_line) {
+                Ok(c) => {
+                    machine.set_program_counter(executed.len());
+                    executed.push(c.to_owned());
+                }
+                Err(_) => {
+                    if !program_line.trim().is_empty() {
+                        eprintln!("invalid command: \"{program_line}\"");
                     }
                 }
             }
+        }
 
-            let c = Command::from_line(&program_line);
-            if c.is_err() {
-                if !program_line.trim().is_empty() {
-                    eprintln!("invalid command: \"{program_line}\"");
-                }
-                continue;
-            }
-            let c = c.unwrap().to_owned();
-            executed.push(c.clone());
-            executed.last().unwrap()
-        };
+        let command = &executed[machine.program_counter()];
+        machine.execute(command, &mut input, &mut output)?;
+    }
+    Ok(())
+}
 
-        // TODO: Deduplicate this code
-        match command {
-            Command::PutThisPancakeOnTop(adjective) => {
-                stack.push(adjective.graphemes(true).count() as u32);
-            }
-            Command::EatThePancakeOnTop => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                stack.pop();
-            }
-            Command::PutTheTopPancakesTogether => {
-                if stack.len() < 2 {
-                    return Err(Error::OutOfPancakes);
-                }
-                let first = stack.pop().unwrap();
-                let second = stack.pop().unwrap();
-                let result = first.checked_add(second).ok_or(Error::PancakeOverflow)?;
-                stack.push(result);
-            }
-            Command::GiveMeAPancake => {
-                input.read_line(&mut in_line)?;
-                let number_input = in_line
-                    .parse()
-                    .map_err(|_| Error::InvalidPancake(in_line.clone()))?;
-                stack.push(number_input);
-                in_line.clear();
-            }
-            Command::HowAboutAHotcake => {
-                let buf = input.fill_buf()?;
-                let number_input = *buf.first().unwrap_or(&0);
-                input.consume(1);
-                stack.push(u32::from(number_input));
-            }
-            Command::ShowMeAPancake => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                let top = stack.last().unwrap();
-                let c = char::from_u32(*top).ok_or(Error::CanNotShowPancake(*top))?;
-                write!(output, "{c}")?;
-            }
-            Command::TakeFromTheTopPancakes => {
-                if stack.len() < 2 {
-                    return Err(Error::OutOfPancakes);
-                }
-                let first = stack.pop().unwrap();
-                let second = stack.pop().unwrap();
-                let result = first.checked_sub(second).ok_or(Error::PancakeUnderflow)?;
-                stack.push(result);
-            }
-            Command::FlipThePancakesOnTop => {
-                if stack.len() < 2 {
-                    return Err(Error::OutOfPancakes);
-                }
-                let first = stack.pop().unwrap();
-                let second = stack.pop().unwrap();
-                stack.push(first);
-                stack.push(second);
-            }
-            Command::PutAnotherPancakeOnTop => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                stack.push(*stack.last().unwrap());
-            }
-            Command::Label(label) => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                let top = stack.last().unwrap();
-                labels.insert(label.clone(), (*top - 1) as usize);
-            }
-            Command::IfThePancakeIsntTastyGoOverTo(target_label) => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                let top = *stack.last().unwrap();
-                if top == 0 {
-                    let label_position = labels
-                        .get(target_label)
-                        .ok_or_else(|| Error::UndefinedLabel(target_label.to_string()))?;
-                    current_statement = Some(*label_position);
-                }
-            }
-            Command::IfThePancakeIsTastyGoOverTo(target_label) => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                let top = *stack.last().unwrap();
-                if top != 0 {
-                    let label_position = labels
-                        .get(target_label)
-                        .ok_or_else(|| Error::UndefinedLabel(target_label.to_string()))?;
-                    current_statement = Some(*label_position);
-                }
-            }
-            Command::PutSyrupOnThePancakes => {
-                for value in &mut stack {
-                    *value = value.checked_add(1).ok_or(Error::PancakeOverflow)?;
-                }
-            }
-            Command::PutButterOnThePancakes => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                let top = stack.last_mut().unwrap();
-                *top = top.checked_add(1).ok_or(Error::PancakeOverflow)?;
-            }
-            Command::TakeOffTheSyrup => {
-                for value in &mut stack {
-                    *value = value.checked_sub(1).ok_or(Error::PancakeUnderflow)?;
-                }
-            }
-            Command::TakeOffTheButter => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                let top = stack.last_mut().unwrap();
-                *top = top.checked_sub(1).ok_or(Error::PancakeUnderflow)?;
-            }
-            Command::EatAllOfThePancakes => {
-                break;
-            }
+fn trim_newline(s: &mut String) {
+    if s.ends_with('\n') {
+        s.pop();
+        if s.ends_with('\r') {
+            s.pop();
         }
     }
-    Ok(())
 }
 
 /// Parses and run the commands contained in the given string using the provided input and output.
@@ -251,149 +123,1076 @@ pub fn run_program_str(program: &str, input: impl Read, output: impl Write) -> R
 pub fn run_program(
     program: &[Command<'_>],
     input: impl Read,
-    mut output: impl Write,
+    output: impl Write,
 ) -> Result<(), Error> {
-    let mut input = BufReader::new(input);
-    let mut in_line = String::new();
+    Machine::new()
+        .run(program, input, output)
+        .map_err(RuntimeError::into_error)
+}
+
+/// Runs the given slice of commands like [`run_program`](./fn.run_program.html), failing as soon as the program exceeds one of the given limits.
+/// ```rust
+/// use pancakestack::{Error, Limits};
+///
+/// let program = pancakestack::parse_program_str("\
+/// Put this ok pancake on top!
+/// [Forever]
+/// Put another pancake on top!
+/// If the pancake is tasty, go over to \"Forever\".");
+///
+/// let limits = Limits::new().with_max_steps(1000).with_max_stack_depth(100);
+/// let error = pancakestack::run_program_with_limits(&program, std::io::empty(), std::io::sink(), limits).unwrap_err();
+/// assert!(matches!(error, Error::StackLimitExceeded(100)));
+/// ```
+///
+/// # Errors
+/// Will return `Err` if the given program performs an illegal operation, exceeds a limit or an io error occurs. See [`Error`](./enum.Error.html).
+pub fn run_program_with_limits(
+    program: &[Command<'_>],
+    input: impl Read,
+    output: impl Write,
+    limits: Limits,
+) -> Result<(), Error> {
+    let mut machine = Machine::new();
+    machine.set_limits(limits);
+    machine
+        .run(program, input, output)
+        .map_err(RuntimeError::into_error)
+}
 
-    let mut stack = Vec::new();
-    let mut labels = HashMap::new();
+/// Runs the given slice of located commands using the provided input and output.
+/// Errors are reported together with the location of the command that failed.
+/// ```rust
+/// let program = pancakestack::parse_program_str_spanned("Put this test pancake on top!\n\nTake from the top pancakes!");
+/// let error = pancakestack::run_program_spanned(&program, std::io::empty(), std::io::sink()).unwrap_err();
+/// assert_eq!(error.line(), 3);
+/// assert_eq!(error.to_string(), "line 3: Out of pancakes! (at command 1 \"Take from the top pancakes!\" with stack [4])");
+/// ```
+///
+/// # Errors
+/// Will return `Err` if the given program performs an illegal operation or an io error occurs. See [`RuntimeError`](./struct.RuntimeError.html).
+pub fn run_program_spanned(
+    program: &[Spanned<Command<'_>>],
+    input: impl Read,
+    output: impl Write,
+) -> Result<(), Spanned<RuntimeError>> {
+    Machine::new().run_spanned(program, input, output)
+}
 
-    let mut current_statement: usize = 0;
-    while let Some(command) = program.get(current_statement) {
-        current_statement += 1;
+/// The state of a running pancakestack program.
+///
+/// A machine owns the pancake stack, the label table, the program counter and the input that has not been consumed yet
+/// and executes one [`Command`](../parse/enum.Command.html) at a time with [`Machine::step`](#method.step).
+/// All `pancakestack::run_*` methods are implemented on top of it.
+/// ```rust
+/// use pancakestack::{Command, Machine, Status};
+///
+/// let program = [
+///     Command::HowAboutAHotcake,
+///     Command::ShowMeAPancake,
+///     Command::EatAllOfThePancakes
+/// ];
+///
+/// let mut output = Vec::new();
+/// let mut machine = Machine::new();
+/// assert_eq!(machine.step(&program[0], &mut output).unwrap(), Status::WaitingForInput);
+///
+/// machine.feed_input(b"a");
+/// let mut trace = Vec::new();
+/// while let Some(command) = program.get(machine.program_counter()) {
+///     trace.push(format!("{command} {:?}", machine.stack()));
+///     if machine.step(command, &mut output).unwrap() == Status::Halted {
+///         break;
+///     }
+/// }
+/// assert_eq!(output, b"a");
+/// assert_eq!(
+///     trace,
+///     ["How about a hotcake? []", "Show me a pancake! [97]", "Eat all of the pancakes! [97]"]
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Machine {
+    stack: Vec<u32>,
+    labels: HashMap<String, usize>,
+    program_counter: usize,
+    halted: bool,
+    input: VecDeque<u8>,
+    input_closed: bool,
+    label_semantics: LabelSemantics,
+    limits: Limits,
+    steps: u64,
+    output_bytes: usize,
+    cancellation_token: Option<CancellationToken>,
+    /// The number of commands executed since the cancellation token was last checked, not part of a continuation.
+    unchecked_steps: u64,
+    paused: bool,
+}
 
-        // TODO: Deduplicate this code
-        match command {
-            Command::PutThisPancakeOnTop(adjective) => {
-                stack.push(adjective.graphemes(true).count() as u32);
+/// The number of commands after which a machine checks its [`CancellationToken`](./struct.CancellationToken.html) again.
+const CANCELLATION_CHECK_INTERVAL: u64 = 1024;
+
+/// The token has not been used.
+const RUNNING: u8 = 0;
+/// [`CancellationToken::pause`](./struct.CancellationToken.html#method.pause) was called and not observed yet.
+const PAUSE_REQUESTED: u8 = 1;
+/// [`CancellationToken::cancel`](./struct.CancellationToken.html#method.cancel) was called.
+const CANCELLED: u8 = 2;
+
+/// Lets other threads stop or pause a [`Machine`](./struct.Machine.html) running a program.
+///
+/// Clones share their state, so a clone can be handed to the machine with [`Machine::set_cancellation_token`](./struct.Machine.html#method.set_cancellation_token)
+/// while another one is kept to interrupt it. The machine checks the token before its first command after the token was set or the machine was
+/// created or [restored](./struct.Machine.html#method.restore), and then before every 1024th command.
+/// ```rust
+/// use pancakestack::{CancellationToken, Error, Machine};
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let program = pancakestack::parse_program_str("Put this ok pancake on top!\n[Forever]\nIf the pancake is tasty, go over to \"Forever\".");
+/// let token = CancellationToken::new();
+/// let mut machine = Machine::new();
+/// machine.set_cancellation_token(token.clone());
+///
+/// let stop_button = thread::spawn(move || {
+///     thread::sleep(Duration::from_millis(10));
+///     token.cancel();
+/// });
+/// let error = machine.run(&program, std::io::empty(), std::io::sink()).unwrap_err();
+/// assert!(matches!(error.error(), Error::Cancelled));
+/// stop_button.join().unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<AtomicU8>,
+}
+
+impl CancellationToken {
+    /// Creates a new token that has neither been cancelled nor paused.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the machine fail with [`Error::Cancelled`](./enum.Error.html#variant.Cancelled) the next time it checks the token.
+    /// A cancelled token stays cancelled.
+    pub fn cancel(&self) {
+        self.state.store(CANCELLED, Ordering::SeqCst);
+    }
+
+    /// Makes the machine pause the next time it checks the token, see [`Status::Paused`](./enum.Status.html#variant.Paused).
+    /// The request is consumed by the pause, so the machine can be resumed with the same token.
+    pub fn pause(&self) {
+        let _ = self.state.compare_exchange(
+            RUNNING,
+            PAUSE_REQUESTED,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+    }
+
+    /// Returns whether [`cancel`](#method.cancel) has been called.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == CANCELLED
+    }
+
+    /// Returns whether [`pause`](#method.pause) has been called and the machine has not paused yet.
+    #[must_use]
+    pub fn is_pause_requested(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == PAUSE_REQUESTED
+    }
+}
+
+/// Bounds on the resources a [`Machine`](./struct.Machine.html) may use, exceeding one fails the command with an [`Error`](./enum.Error.html).
+///
+/// Every limit is unset by default. The deadline is checked before each command, so a command blocking on input is not interrupted.
+/// ```rust
+/// use pancakestack::{Error, Limits, Machine};
+/// use std::time::{Duration, Instant};
+///
+/// let program = pancakestack::parse_program_str("Put this good pancake on top!\nShow me a pancake!\nShow me a pancake!");
+/// let mut machine = Machine::new();
+/// machine.set_limits(Limits::new().with_max_output_bytes(1).with_deadline(Instant::now() + Duration::from_secs(1)));
+/// let error = machine.run(&program, std::io::empty(), std::io::sink()).unwrap_err();
+/// assert!(matches!(error.error(), Error::OutputLimitExceeded(1)));
+/// assert_eq!(error.program_counter(), 2);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    max_steps: Option<u64>,
+    max_stack_depth: Option<usize>,
+    max_output_bytes: Option<usize>,
+    deadline: Option<Instant>,
+}
+
+impl Limits {
+    /// Creates limits without any bounds.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of commands that may be executed, see [`StepLimitExceeded`](./enum.Error.html#variant.StepLimitExceeded).
+    #[must_use]
+    pub fn with_max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Sets the maximum number of pancakes on the stack, see [`StackLimitExceeded`](./enum.Error.html#variant.StackLimitExceeded).
+    #[must_use]
+    pub fn with_max_stack_depth(mut self, max_stack_depth: usize) -> Self {
+        self.max_stack_depth = Some(max_stack_depth);
+        self
+    }
+
+    /// Sets the maximum number of bytes that may be written to the output, see [`OutputLimitExceeded`](./enum.Error.html#variant.OutputLimitExceeded).
+    #[must_use]
+    pub fn with_max_output_bytes(mut self, max_output_bytes: usize) -> Self {
+        self.max_output_bytes = Some(max_output_bytes);
+        self
+    }
+
+    /// Sets the point in time after which no more commands may be executed, see [`DeadlineExceeded`](./enum.Error.html#variant.DeadlineExceeded).
+    #[must_use]
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    #[must_use]
+    pub fn max_steps(&self) -> Option<u64> {
+        self.max_steps
+    }
+
+    #[must_use]
+    pub fn max_stack_depth(&self) -> Option<usize> {
+        self.max_stack_depth
+    }
+
+    #[must_use]
+    pub fn max_output_bytes(&self) -> Option<usize> {
+        self.max_output_bytes
+    }
+
+    #[must_use]
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+}
+
+/// Where a jump to a [`Label`](../parse/enum.Command.html#variant.Label) continues.
+/// ```rust
+/// use pancakestack::{LabelSemantics, Machine};
+///
+/// let program = pancakestack::parse_program_str("\
+/// Put this rich pancake on top!
+/// [Show]
+/// Take off the butter!
+/// Show me a pancake!
+/// Take off the butter!
+/// If the pancake is tasty, go over to \"Show\".");
+///
+/// // the top pancake is 4 when the label is declared, so jumps continue with the fourth command
+/// let mut output = Vec::new();
+/// Machine::new().run(&program, std::io::empty(), &mut output).unwrap();
+/// assert_eq!(output, b"\x03\x02\x01");
+///
+/// let mut output = Vec::new();
+/// Machine::with_label_semantics(LabelSemantics::NextCommand)
+///     .run(&program, std::io::empty(), &mut output)
+///     .unwrap();
+/// assert_eq!(output, b"\x03\x01");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LabelSemantics {
+    /// Jumps continue with the command whose one-based index is the top pancake when the label was declared, as described by the specification.
+    /// Declaring a label fails with [`InvalidLabelPosition`](./enum.Error.html#variant.InvalidLabelPosition) if the top pancake is zero.
+    #[default]
+    TopPancake,
+    /// Jumps continue with the command after the label, no matter what pancakes are on the stack.
+    NextCommand,
+}
+
+/// The outcome of a single [`Machine::step`](./struct.Machine.html#method.step).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// The command was executed and the program continues with the next command.
+    Continued,
+    /// The command was executed and the program continues at the contained command index.
+    Jumped(usize),
+    /// The program was terminated by [`Command::EatAllOfThePancakes`](../parse/enum.Command.html#variant.EatAllOfThePancakes).
+    Halted,
+    /// The command needs more input than is available.
+    /// Nothing was executed, the command can be retried after calling [`Machine::feed_input`](./struct.Machine.html#method.feed_input) or [`Machine::close_input`](./struct.Machine.html#method.close_input).
+    WaitingForInput,
+    /// The machine was paused with [`CancellationToken::pause`](./struct.CancellationToken.html#method.pause).
+    /// Nothing was executed, the command is executed when the machine is stepped or run again.
+    Paused,
+}
+
+impl Machine {
+    /// Creates a new machine with an empty stack, starting at the first command.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new machine like [`new`](#method.new) that resolves labels with the given semantics.
+    #[must_use]
+    pub fn with_label_semantics(label_semantics: LabelSemantics) -> Self {
+        Machine {
+            label_semantics,
+            ..Self::default()
+        }
+    }
+
+    /// Returns how the machine resolves labels.
+    #[must_use]
+    pub fn label_semantics(&self) -> LabelSemantics {
+        self.label_semantics
+    }
+
+    /// Returns the limits the machine enforces.
+    #[must_use]
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Sets the limits the machine enforces from now on. Commands and output counted so far count towards the new limits.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Returns the number of commands executed so far.
+    #[must_use]
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Sets the token other threads can use to stop or pause the machine, see [`CancellationToken`](./struct.CancellationToken.html).
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation_token = Some(token);
+        self.unchecked_steps = 0;
+    }
+
+    /// Returns whether the last command was not executed because the machine was paused.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Captures the state of the machine, so that it can be stored and resumed later with [`from_continuation`](#method.from_continuation).
+    ///
+    /// The limits and the cancellation token are not part of the continuation.
+    /// ```rust
+    /// use pancakestack::{CancellationToken, Continuation, Machine};
+    ///
+    /// let program = pancakestack::parse_program_str("Put this good pancake on top!\nShow me a pancake!");
+    /// let token = CancellationToken::new();
+    /// let mut machine = Machine::new();
+    /// machine.set_cancellation_token(token.clone());
+    ///
+    /// token.pause();
+    /// machine.run(&program, std::io::empty(), std::io::sink()).unwrap();
+    /// assert!(machine.is_paused());
+    /// let bytes = machine.continuation().to_bytes();
+    ///
+    /// let mut resumed = Machine::from_continuation(Continuation::from_bytes(&bytes).unwrap());
+    /// let mut output = Vec::new();
+    /// resumed.run(&program, std::io::empty(), &mut output).unwrap();
+    /// assert_eq!(output, b"\x04");
+    /// ```
+    #[must_use]
+    pub fn continuation(&self) -> Continuation {
+        Continuation {
+            stack: self.stack.clone(),
+            labels: self.labels.clone(),
+            program_counter: self.program_counter,
+            halted: self.halted,
+            input: self.input.clone(),
+            input_closed: self.input_closed,
+            label_semantics: self.label_semantics,
+            steps: self.steps,
+            output_bytes: self.output_bytes,
+        }
+    }
+
+    /// Returns the stack, the labels, the program counter, the pending input and the counters of the limits encoded as bytes,
+    /// the [encoded](./struct.Continuation.html#method.to_bytes) [`continuation`](#method.continuation).
+    /// The machine can be set back to this state with [`restore`](#method.restore).
+    /// ```rust
+    /// use pancakestack::{Error, Limits, Machine};
+    ///
+    /// let program = pancakestack::parse_program_str("\
+    /// Put this good pancake on top!
+    /// Put butter on the pancakes!
+    /// Show me a pancake!");
+    /// let mut machine = Machine::new();
+    /// machine.set_limits(Limits::new().with_max_steps(2));
+    /// let error = machine.run(&program, std::io::empty(), std::io::sink()).unwrap_err();
+    /// assert!(matches!(error.error(), Error::StepLimitExceeded(2)));
+    /// let snapshot = machine.snapshot();
+    ///
+    /// let mut restored = Machine::new();
+    /// restored.restore(&snapshot).unwrap();
+    /// assert_eq!(restored.stack(), &[5]);
+    /// let mut output = Vec::new();
+    /// restored.run(&program, std::io::empty(), &mut output).unwrap();
+    /// assert_eq!(output, b"\x05");
+    /// ```
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<u8> {
+        self.continuation().to_bytes()
+    }
+
+    /// Sets the machine to the state encoded in the given [`snapshot`](#method.snapshot).
+    /// The limits and the cancellation token of the machine are kept, the restored counters count towards the limits.
+    ///
+    /// # Errors
+    /// Will return `Err` if the bytes are not a snapshot encoded by this version, the machine is left unchanged then. See [`ContinuationError`](./enum.ContinuationError.html).
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<(), ContinuationError> {
+        let continuation = Continuation::from_bytes(snapshot)?;
+        *self = Machine {
+            limits: self.limits,
+            cancellation_token: self.cancellation_token.take(),
+            ..Self::from_continuation(continuation)
+        };
+        Ok(())
+    }
+
+    /// Creates a machine continuing from the given [`Continuation`](./struct.Continuation.html), without limits or cancellation token.
+    #[must_use]
+    pub fn from_continuation(continuation: Continuation) -> Self {
+        Machine {
+            stack: continuation.stack,
+            labels: continuation.labels,
+            program_counter: continuation.program_counter,
+            halted: continuation.halted,
+            input: continuation.input,
+            input_closed: continuation.input_closed,
+            label_semantics: continuation.label_semantics,
+            steps: continuation.steps,
+            output_bytes: continuation.output_bytes,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the index of the next command to be executed.
+    #[must_use]
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    /// Sets the index of the next command to be executed.
+    pub fn set_program_counter(&mut self, program_counter: usize) {
+        self.program_counter = program_counter;
+    }
+
+    /// Returns the pancake stack, the top pancake being the last element.
+    #[must_use]
+    pub fn stack(&self) -> &[u32] {
+        &self.stack
+    }
+
+    /// Returns the defined labels and the command index they jump to.
+    #[must_use]
+    pub fn labels(&self) -> &HashMap<String, usize> {
+        &self.labels
+    }
+
+    /// Returns the input that has been fed to the machine but not consumed yet.
+    #[must_use]
+    pub fn pending_input(&self) -> &VecDeque<u8> {
+        &self.input
+    }
+
+    /// Appends the given bytes to the input of the program.
+    pub fn feed_input(&mut self, input: &[u8]) {
+        self.input.extend(input);
+    }
+
+    /// Marks the end of the input. Commands reading input will no longer wait for more input once the pending input is consumed.
+    pub fn close_input(&mut self) {
+        self.input_closed = true;
+    }
+
+    /// Returns whether [`close_input`](#method.close_input) has been called.
+    #[must_use]
+    pub fn is_input_closed(&self) -> bool {
+        self.input_closed
+    }
+
+    /// Returns whether the machine executed [`Command::EatAllOfThePancakes`](../parse/enum.Command.html#variant.EatAllOfThePancakes).
+    #[must_use]
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Runs the given slice of commands from the current program counter until the program halts, runs past its end or is [paused](./struct.CancellationToken.html#method.pause).
+    /// Input is read from the given [`Read`](https://doc.rust-lang.org/std/io/trait.Read.html) whenever the pending input is exhausted.
+    ///
+    /// # Errors
+    /// Will return `Err` if the given program performs an illegal operation or an io error occurs.
+    /// The returned [`RuntimeError`](./struct.RuntimeError.html) describes the failing command and the stack at that point.
+    pub fn run(
+        &mut self,
+        program: &[Command<'_>],
+        mut input: impl Read,
+        mut output: impl Write,
+    ) -> Result<(), RuntimeError> {
+        while !self.halted {
+            let command = match program.get(self.program_counter) {
+                Some(command) => command,
+                None => break,
+            };
+            let status = self
+                .execute(command, &mut input, &mut output)
+                .map_err(|error| self.runtime_error(error, command))?;
+            if status == Status::Paused {
+                break;
             }
-            Command::EatThePancakeOnTop => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                stack.pop();
+        }
+        Ok(())
+    }
+
+    /// Runs the given slice of located commands like [`run`](#method.run), reporting errors together with the location of the command that failed.
+    ///
+    /// # Errors
+    /// Will return `Err` if the given program performs an illegal operation or an io error occurs. See [`RuntimeError`](./struct.RuntimeError.html).
+    pub fn run_spanned(
+        &mut self,
+        program: &[Spanned<Command<'_>>],
+        mut input: impl Read,
+        mut output: impl Write,
+    ) -> Result<(), Spanned<RuntimeError>> {
+        while !self.halted {
+            let command = match program.get(self.program_counter) {
+                Some(command) => command,
+                None => break,
+            };
+            let status = self
+                .execute(command.node(), &mut input, &mut output)
+                .map_err(|error| {
+                    let error = self.runtime_error(error, command.node());
+                    Spanned::new(error, command.line(), command.span())
+                })?;
+            if status == Status::Paused {
+                break;
             }
-            Command::PutTheTopPancakesTogether => {
-                if stack.len() < 2 {
-                    return Err(Error::OutOfPancakes);
+        }
+        Ok(())
+    }
+
+    /// Captures the current state of the machine for an error caused by the given command.
+    fn runtime_error(&self, error: Error, command: &Command<'_>) -> RuntimeError {
+        RuntimeError {
+            error,
+            program_counter: self.program_counter,
+            command: command.to_owned(),
+            stack: self.stack.clone(),
+        }
+    }
+
+    /// Executes the given command, reading from the given input until the command does not wait for input anymore.
+    pub(crate) fn execute(
+        &mut self,
+        command: &Command<'_>,
+        input: &mut impl Read,
+        output: &mut impl Write,
+    ) -> Result<Status, Error> {
+        loop {
+            match self.step(command, output)? {
+                Status::WaitingForInput => self.read_input(input)?,
+                status => return Ok(status),
+            }
+        }
+    }
+
+    fn read_input(&mut self, input: &mut impl Read) -> io::Result<()> {
+        let mut buf = [0; 1024];
+        let length = loop {
+            match input.read(&mut buf) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+        if length == 0 {
+            self.close_input();
+        } else {
+            self.feed_input(&buf[..length]);
+        }
+        Ok(())
+    }
+
+    /// Executes a single command and advances the program counter.
+    /// The command is usually the one at [`program_counter`](#method.program_counter) but any command can be executed.
+    ///
+    /// If an error is returned the stack is left untouched and the program counter is not advanced.
+    ///
+    /// # Errors
+    /// Will return `Err` if the command performs an illegal operation or an io error occurs. See [`Error`](./enum.Error.html).
+    pub fn step(
+        &mut self,
+        command: &Command<'_>,
+        output: &mut impl Write,
+    ) -> Result<Status, Error> {
+        if let Some(token) = &self.cancellation_token {
+            if self.unchecked_steps == 0 {
+                if token.is_cancelled() {
+                    return Err(Error::Cancelled);
                 }
-                let first = stack.pop().unwrap();
-                let second = stack.pop().unwrap();
-                let result = first.checked_add(second).ok_or(Error::PancakeOverflow)?;
-                stack.push(result);
+                // only the pause observing the request consumes it
+                let consumed = token.state.compare_exchange(
+                    PAUSE_REQUESTED,
+                    RUNNING,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                );
+                if consumed.is_ok() {
+                    self.paused = true;
+                    return Ok(Status::Paused);
+                }
+            }
+        }
+        self.paused = false;
+        self.check_limits(command)?;
+        let stack = &mut self.stack;
+        let mut status = Status::Continued;
+
+        match command {
+            Command::PutThisPancakeOnTop(adjective) => {
+                stack.push(adjective.graphemes(true).count() as u32);
             }
+            Command::EatThePancakeOnTop => eat(stack)?,
+            Command::PutTheTopPancakesTogether => put_together(stack)?,
             Command::GiveMeAPancake => {
-                input.read_line(&mut in_line)?;
-                let number_input = in_line
-                    .parse()
-                    .map_err(|_| Error::InvalidPancake(in_line.clone()))?;
-                stack.push(number_input);
-                in_line.clear();
+                let line_length = match self.input.iter().position(|&b| b == b'\n') {
+                    Some(index) => index + 1,
+                    None if self.input_closed => self.input.len(),
+                    None => return Ok(Status::WaitingForInput),
+                };
+                let line = self.input.drain(..line_length).collect::<Vec<_>>();
+                stack.push(parse_pancake(&line)?);
             }
             Command::HowAboutAHotcake => {
-                let buf = input.fill_buf()?;
-                let number_input = *buf.first().unwrap_or(&0);
-                input.consume(1);
+                let number_input = match self.input.pop_front() {
+                    Some(byte) => byte,
+                    None if self.input_closed => 0,
+                    None => return Ok(Status::WaitingForInput),
+                };
                 stack.push(u32::from(number_input));
             }
             Command::ShowMeAPancake => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
+                let c = show(stack)?;
+                if let Some(max_output_bytes) = self.limits.max_output_bytes {
+                    if self.output_bytes + c.len_utf8() > max_output_bytes {
+                        return Err(Error::OutputLimitExceeded(max_output_bytes));
+                    }
                 }
-                let top = stack.last().unwrap();
-                let c = char::from_u32(*top).ok_or(Error::CanNotShowPancake(*top))?;
                 write!(output, "{c}")?;
+                self.output_bytes += c.len_utf8();
             }
-            Command::TakeFromTheTopPancakes => {
-                if stack.len() < 2 {
-                    return Err(Error::OutOfPancakes);
-                }
-                let first = stack.pop().unwrap();
-                let second = stack.pop().unwrap();
-                let result = first.checked_sub(second).ok_or(Error::PancakeUnderflow)?;
-                stack.push(result);
-            }
-            Command::FlipThePancakesOnTop => {
-                if stack.len() < 2 {
-                    return Err(Error::OutOfPancakes);
-                }
-                let first = stack.pop().unwrap();
-                let second = stack.pop().unwrap();
-                stack.push(first);
-                stack.push(second);
-            }
-            Command::PutAnotherPancakeOnTop => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                stack.push(*stack.last().unwrap());
-            }
+            Command::TakeFromTheTopPancakes => take_from(stack)?,
+            Command::FlipThePancakesOnTop => flip(stack)?,
+            Command::PutAnotherPancakeOnTop => put_another(stack)?,
             Command::Label(label) => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
+                let position = match self.label_semantics {
+                    LabelSemantics::TopPancake => label_position(stack, label)?,
+                    LabelSemantics::NextCommand => self.program_counter + 1,
+                };
+                // avoid allocating the label name if it is already known
+                if let Some(known_position) = self.labels.get_mut(label.as_ref()) {
+                    *known_position = position;
+                } else {
+                    self.labels.insert(label.to_string(), position);
                 }
-                let top = stack.last().unwrap();
-                labels.insert(label, (*top - 1) as usize);
             }
             Command::IfThePancakeIsntTastyGoOverTo(target_label) => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                let top = *stack.last().unwrap();
-                if top == 0 {
-                    let label_position = labels
-                        .get(target_label)
-                        .ok_or_else(|| Error::UndefinedLabel((*target_label).to_string()))?;
-                    current_statement = *label_position;
+                if top(stack)? == 0 {
+                    status = Status::Jumped(self.label_position(target_label)?);
                 }
             }
             Command::IfThePancakeIsTastyGoOverTo(target_label) => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                let top = *stack.last().unwrap();
-                if top != 0 {
-                    let label_position = labels
-                        .get(target_label)
-                        .ok_or_else(|| Error::UndefinedLabel((*target_label).to_string()))?;
-                    current_statement = *label_position;
+                if top(stack)? != 0 {
+                    status = Status::Jumped(self.label_position(target_label)?);
                 }
             }
-            Command::PutSyrupOnThePancakes => {
-                for value in &mut stack {
-                    *value = value.checked_add(1).ok_or(Error::PancakeOverflow)?;
-                }
+            Command::PutSyrupOnThePancakes => put_syrup(stack)?,
+            Command::PutButterOnThePancakes => put_butter(stack)?,
+            Command::TakeOffTheSyrup => take_off_syrup(stack)?,
+            Command::TakeOffTheButter => take_off_butter(stack)?,
+            Command::EatAllOfThePancakes => {
+                self.halted = true;
+                status = Status::Halted;
             }
-            Command::PutButterOnThePancakes => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                let top = stack.last_mut().unwrap();
-                *top = top.checked_add(1).ok_or(Error::PancakeOverflow)?;
+        }
+
+        self.steps += 1;
+        self.unchecked_steps = (self.unchecked_steps + 1) % CANCELLATION_CHECK_INTERVAL;
+        self.program_counter = match status {
+            Status::Jumped(target) => target,
+            _ => self.program_counter + 1,
+        };
+        Ok(status)
+    }
+
+    /// Fails if executing the given command would exceed a limit that does not depend on the outcome of the command.
+    fn check_limits(&self, command: &Command<'_>) -> Result<(), Error> {
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps >= max_steps {
+                return Err(Error::StepLimitExceeded(max_steps));
             }
-            Command::TakeOffTheSyrup => {
-                for value in &mut stack {
-                    *value = value.checked_sub(1).ok_or(Error::PancakeUnderflow)?;
-                }
+        }
+        if let Some(max_stack_depth) = self.limits.max_stack_depth {
+            let pushes = matches!(
+                command,
+                Command::PutThisPancakeOnTop(_)
+                    | Command::PutAnotherPancakeOnTop
+                    | Command::GiveMeAPancake
+                    | Command::HowAboutAHotcake
+            );
+            if pushes && self.stack.len() >= max_stack_depth {
+                return Err(Error::StackLimitExceeded(max_stack_depth));
             }
-            Command::TakeOffTheButter => {
-                if stack.is_empty() {
-                    return Err(Error::OutOfPancakes);
-                }
-                let top = stack.last_mut().unwrap();
-                *top = top.checked_sub(1).ok_or(Error::PancakeUnderflow)?;
+        }
+        if let Some(deadline) = self.limits.deadline {
+            if Instant::now() >= deadline {
+                return Err(Error::DeadlineExceeded);
             }
-            Command::EatAllOfThePancakes => {
-                break;
+        }
+        Ok(())
+    }
+
+    fn label_position(&self, label: &str) -> Result<usize, Error> {
+        self.labels
+            .get(label)
+            .copied()
+            .ok_or_else(|| Error::UndefinedLabel(label.to_string()))
+    }
+}
+
+/// The first bytes of every encoded [`Continuation`](./struct.Continuation.html).
+const CONTINUATION_MAGIC: &[u8; 4] = b"PCSC";
+/// The version of the encoding of [`Continuation`](./struct.Continuation.html)s.
+const CONTINUATION_VERSION: u8 = 1;
+
+const FLAG_HALTED: u8 = 1;
+const FLAG_INPUT_CLOSED: u8 = 1 << 1;
+const FLAG_NEXT_COMMAND_LABELS: u8 = 1 << 2;
+
+/// The state of a [`Machine`](./struct.Machine.html) that does not depend on the program or the threads using it, see [`Machine::continuation`](./struct.Machine.html#method.continuation).
+///
+/// It contains the stack, the labels, the program counter, the pending input and how labels are resolved,
+/// and can be converted to bytes with [`to_bytes`](#method.to_bytes) and back with [`from_bytes`](#method.from_bytes) to store it or send it elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Continuation {
+    stack: Vec<u32>,
+    labels: HashMap<String, usize>,
+    program_counter: usize,
+    halted: bool,
+    input: VecDeque<u8>,
+    input_closed: bool,
+    label_semantics: LabelSemantics,
+    steps: u64,
+    output_bytes: usize,
+}
+
+impl Continuation {
+    /// Returns the index of the next command to be executed.
+    #[must_use]
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    /// Returns the pancake stack, the top pancake being the last element.
+    #[must_use]
+    pub fn stack(&self) -> &[u32] {
+        &self.stack
+    }
+
+    /// Returns whether the program has been halted.
+    #[must_use]
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Encodes the continuation. Labels are encoded in order of their names, so equal continuations are encoded to equal bytes.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = CONTINUATION_MAGIC.to_vec();
+        bytes.push(CONTINUATION_VERSION);
+        let mut flags = 0;
+        if self.halted {
+            flags |= FLAG_HALTED;
+        }
+        if self.input_closed {
+            flags |= FLAG_INPUT_CLOSED;
+        }
+        if self.label_semantics == LabelSemantics::NextCommand {
+            flags |= FLAG_NEXT_COMMAND_LABELS;
+        }
+        bytes.push(flags);
+        bytes.extend((self.program_counter as u64).to_le_bytes());
+        bytes.extend(self.steps.to_le_bytes());
+        bytes.extend((self.output_bytes as u64).to_le_bytes());
+
+        bytes.extend((self.stack.len() as u64).to_le_bytes());
+        for pancake in &self.stack {
+            bytes.extend(pancake.to_le_bytes());
+        }
+        let mut labels = self.labels.iter().collect::<Vec<_>>();
+        labels.sort();
+        bytes.extend((labels.len() as u64).to_le_bytes());
+        for (label, &position) in labels {
+            bytes.extend((label.len() as u64).to_le_bytes());
+            bytes.extend(label.as_bytes());
+            bytes.extend((position as u64).to_le_bytes());
+        }
+        bytes.extend((self.input.len() as u64).to_le_bytes());
+        bytes.extend(&self.input);
+        bytes
+    }
+
+    /// Decodes a continuation encoded with [`to_bytes`](#method.to_bytes).
+    ///
+    /// # Errors
+    /// Will return `Err` if the bytes are not a continuation encoded by this version. See [`ContinuationError`](./enum.ContinuationError.html).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ContinuationError> {
+        let mut decoder = Decoder { bytes };
+        if decoder.take(CONTINUATION_MAGIC.len())? != CONTINUATION_MAGIC {
+            return Err(ContinuationError::InvalidFormat);
+        }
+        let version = decoder.u8()?;
+        if version != CONTINUATION_VERSION {
+            return Err(ContinuationError::UnsupportedVersion(version));
+        }
+        let flags = decoder.u8()?;
+        if flags & !(FLAG_HALTED | FLAG_INPUT_CLOSED | FLAG_NEXT_COMMAND_LABELS) != 0 {
+            return Err(ContinuationError::InvalidFormat);
+        }
+        let program_counter = decoder.usize()?;
+        let steps = decoder.u64()?;
+        let output_bytes = decoder.usize()?;
+
+        let stack = (0..decoder.len(4)?)
+            .map(|_| decoder.u32())
+            .collect::<Result<_, _>>()?;
+        let mut labels = HashMap::new();
+        for _ in 0..decoder.len(16)? {
+            let length = decoder.len(1)?;
+            let label = std::str::from_utf8(decoder.take(length)?)
+                .map_err(|_| ContinuationError::InvalidFormat)?;
+            labels.insert(label.to_string(), decoder.usize()?);
+        }
+        let length = decoder.len(1)?;
+        let input = decoder.take(length)?.iter().copied().collect();
+        if !decoder.bytes.is_empty() {
+            return Err(ContinuationError::InvalidFormat);
+        }
+
+        Ok(Continuation {
+            stack,
+            labels,
+            program_counter,
+            halted: flags & FLAG_HALTED != 0,
+            input,
+            input_closed: flags & FLAG_INPUT_CLOSED != 0,
+            label_semantics: if flags & FLAG_NEXT_COMMAND_LABELS != 0 {
+                LabelSemantics::NextCommand
+            } else {
+                LabelSemantics::TopPancake
+            },
+            steps,
+            output_bytes,
+        })
+    }
+}
+
+/// Reads the parts of an encoded [`Continuation`](./struct.Continuation.html).
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ContinuationError> {
+        if self.bytes.len() < length {
+            return Err(ContinuationError::InvalidFormat);
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, ContinuationError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ContinuationError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, ContinuationError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn usize(&mut self) -> Result<usize, ContinuationError> {
+        usize::try_from(self.u64()?).map_err(|_| ContinuationError::InvalidFormat)
+    }
+
+    /// Reads the number of elements of a sequence, failing if the remaining bytes are too short for elements of at least the given size.
+    fn len(&mut self, element_size: usize) -> Result<usize, ContinuationError> {
+        let length = self.usize()?;
+        if length.saturating_mul(element_size) > self.bytes.len() {
+            return Err(ContinuationError::InvalidFormat);
+        }
+        Ok(length)
+    }
+}
+
+/// An error decoding a [`Continuation`](./struct.Continuation.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContinuationError {
+    /// The bytes are not an encoded continuation.
+    InvalidFormat,
+    /// The continuation was encoded by a different version of this crate.
+    UnsupportedVersion(u8),
+}
+impl Display for ContinuationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContinuationError::InvalidFormat => write!(f, "Invalid continuation."),
+            ContinuationError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported continuation version {version}.")
             }
         }
     }
+}
+impl std::error::Error for ContinuationError {}
+
+// The effects of the commands on the stack, shared with the bytecode interpreter.
+
+/// Returns the top pancake.
+pub(crate) fn top(stack: &[u32]) -> Result<u32, Error> {
+    stack.last().copied().ok_or(Error::OutOfPancakes)
+}
+
+/// Returns the top pancake and the one below it.
+fn top_two(stack: &[u32]) -> Result<(u32, u32), Error> {
+    match stack {
+        [.., second, first] => Ok((*first, *second)),
+        _ => Err(Error::OutOfPancakes),
+    }
+}
+
+/// Removes the top pancake. See [`EatThePancakeOnTop`](../parse/enum.Command.html#variant.EatThePancakeOnTop).
+pub(crate) fn eat(stack: &mut Vec<u32>) -> Result<(), Error> {
+    stack.pop().map(drop).ok_or(Error::OutOfPancakes)
+}
+
+/// Replaces the top two pancakes with their sum. See [`PutTheTopPancakesTogether`](../parse/enum.Command.html#variant.PutTheTopPancakesTogether).
+pub(crate) fn put_together(stack: &mut Vec<u32>) -> Result<(), Error> {
+    let (first, second) = top_two(stack)?;
+    let result = first.checked_add(second).ok_or(Error::PancakeOverflow)?;
+    stack.truncate(stack.len() - 2);
+    stack.push(result);
+    Ok(())
+}
+
+/// Replaces the top two pancakes with the top one minus the second one. See [`TakeFromTheTopPancakes`](../parse/enum.Command.html#variant.TakeFromTheTopPancakes).
+pub(crate) fn take_from(stack: &mut Vec<u32>) -> Result<(), Error> {
+    let (first, second) = top_two(stack)?;
+    let result = first.checked_sub(second).ok_or(Error::PancakeUnderflow)?;
+    stack.truncate(stack.len() - 2);
+    stack.push(result);
+    Ok(())
+}
+
+/// Swaps the top two pancakes. See [`FlipThePancakesOnTop`](../parse/enum.Command.html#variant.FlipThePancakesOnTop).
+pub(crate) fn flip(stack: &mut [u32]) -> Result<(), Error> {
+    if stack.len() < 2 {
+        return Err(Error::OutOfPancakes);
+    }
+    let len = stack.len();
+    stack.swap(len - 1, len - 2);
+    Ok(())
+}
+
+/// Pushes a copy of the top pancake. See [`PutAnotherPancakeOnTop`](../parse/enum.Command.html#variant.PutAnotherPancakeOnTop).
+pub(crate) fn put_another(stack: &mut Vec<u32>) -> Result<(), Error> {
+    let top = top(stack)?;
+    stack.push(top);
+    Ok(())
+}
+
+/// Increments every pancake. See [`PutSyrupOnThePancakes`](../parse/enum.Command.html#variant.PutSyrupOnThePancakes).
+pub(crate) fn put_syrup(stack: &mut [u32]) -> Result<(), Error> {
+    if stack.contains(&u32::MAX) {
+        return Err(Error::PancakeOverflow);
+    }
+    for value in stack.iter_mut() {
+        *value += 1;
+    }
+    Ok(())
+}
+
+/// Decrements every pancake. See [`TakeOffTheSyrup`](../parse/enum.Command.html#variant.TakeOffTheSyrup).
+pub(crate) fn take_off_syrup(stack: &mut [u32]) -> Result<(), Error> {
+    if stack.contains(&0) {
+        return Err(Error::PancakeUnderflow);
+    }
+    for value in stack.iter_mut() {
+        *value -= 1;
+    }
+    Ok(())
+}
+
+/// Increments the top pancake. See [`PutButterOnThePancakes`](../parse/enum.Command.html#variant.PutButterOnThePancakes).
+pub(crate) fn put_butter(stack: &mut [u32]) -> Result<(), Error> {
+    let top = stack.last_mut().ok_or(Error::OutOfPancakes)?;
+    *top = top.checked_add(1).ok_or(Error::PancakeOverflow)?;
+    Ok(())
+}
+
+/// Decrements the top pancake. See [`TakeOffTheButter`](../parse/enum.Command.html#variant.TakeOffTheButter).
+pub(crate) fn take_off_butter(stack: &mut [u32]) -> Result<(), Error> {
+    let top = stack.last_mut().ok_or(Error::OutOfPancakes)?;
+    *top = top.checked_sub(1).ok_or(Error::PancakeUnderflow)?;
     Ok(())
 }
 
+/// Parses a line of input, including its line break, into a pancake. See [`GiveMeAPancake`](../parse/enum.Command.html#variant.GiveMeAPancake).
+pub(crate) fn parse_pancake(line: &[u8]) -> Result<u32, Error> {
+    let line = String::from_utf8_lossy(line);
+    line.trim_end_matches(&['\n', '\r'][..])
+        .parse()
+        .map_err(|_| Error::InvalidPancake(line.to_string()))
+}
+
+/// Returns the char the top pancake is shown as. See [`ShowMeAPancake`](../parse/enum.Command.html#variant.ShowMeAPancake).
+pub(crate) fn show(stack: &[u32]) -> Result<char, Error> {
+    let top = top(stack)?;
+    char::from_u32(top).ok_or(Error::CanNotShowPancake(top))
+}
+
+/// Returns the position the given label declared on the top pancake points to,
+/// with [`LabelSemantics::TopPancake`](./enum.LabelSemantics.html#variant.TopPancake).
+pub(crate) fn label_position(stack: &[u32], label: &str) -> Result<usize, Error> {
+    let top = top(stack)?;
+    let position = top
+        .checked_sub(1)
+        .ok_or_else(|| Error::InvalidLabelPosition(label.to_string()))?;
+    Ok(position as usize)
+}
+
 /// An enum representing the possible errors when executing a pancakestack program.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// You were greedy and wanted more pancakes than were available.
     OutOfPancakes,
@@ -403,6 +1202,18 @@ pub enum Error {
     CanNotShowPancake(u32),
     /// You tried to go somewhere undefined.
     UndefinedLabel(String),
+    /// You declared a label on a zero pancake, which would point before the first command.
+    InvalidLabelPosition(String),
+    /// You kept cooking for more than the given number of commands. See [`Limits::with_max_steps`](./struct.Limits.html#method.with_max_steps).
+    StepLimitExceeded(u64),
+    /// You stacked more than the given number of pancakes. See [`Limits::with_max_stack_depth`](./struct.Limits.html#method.with_max_stack_depth).
+    StackLimitExceeded(usize),
+    /// You showed more than the given number of bytes. See [`Limits::with_max_output_bytes`](./struct.Limits.html#method.with_max_output_bytes).
+    OutputLimitExceeded(usize),
+    /// You were still cooking when the deadline passed. See [`Limits::with_deadline`](./struct.Limits.html#method.with_deadline).
+    DeadlineExceeded,
+    /// Someone told you to stop cooking. See [`CancellationToken::cancel`](./struct.CancellationToken.html#method.cancel).
+    Cancelled,
     /// You tried to produce an invalid pancake by underflowing u32.
     PancakeUnderflow,
     /// You tried to produce an invalid pancake by overflowing u32.
@@ -410,6 +1221,28 @@ pub enum Error {
     /// An Io Error occured while reading from the provided [`Read`](https://doc.rust-lang.org/std/io/trait.Read.html) or writing from the provided [`Write`](https://doc.rust-lang.org/std/io/trait.Write.html).
     Io(io::Error),
 }
+
+impl Error {
+    /// Returns the diagnostic code identifying the kind of this error.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::OutOfPancakes => "E0101",
+            Error::InvalidPancake(_) => "E0102",
+            Error::CanNotShowPancake(_) => "E0103",
+            Error::UndefinedLabel(_) => "E0104",
+            Error::PancakeUnderflow => "E0105",
+            Error::PancakeOverflow => "E0106",
+            Error::Io(_) => "E0107",
+            Error::InvalidLabelPosition(_) => "E0108",
+            Error::StepLimitExceeded(_) => "E0109",
+            Error::StackLimitExceeded(_) => "E0110",
+            Error::OutputLimitExceeded(_) => "E0111",
+            Error::DeadlineExceeded => "E0112",
+            Error::Cancelled => "E0113",
+        }
+    }
+}
 impl Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -419,6 +1252,18 @@ impl Display for Error {
                 write!(f, "Pancake can not be shown (invalid char): {p}")
             }
             Error::UndefinedLabel(l) => write!(f, "Use of undefined label \"{l}\""),
+            Error::InvalidLabelPosition(l) => {
+                write!(f, "Label \"{l}\" can not be declared on a zero pancake")
+            }
+            Error::StepLimitExceeded(limit) => write!(f, "Exceeded the limit of {limit} commands."),
+            Error::StackLimitExceeded(limit) => {
+                write!(f, "Exceeded the limit of {limit} pancakes.")
+            }
+            Error::OutputLimitExceeded(limit) => {
+                write!(f, "Exceeded the limit of {limit} output bytes.")
+            }
+            Error::DeadlineExceeded => write!(f, "Exceeded the deadline."),
+            Error::Cancelled => write!(f, "Cancelled."),
             Error::PancakeUnderflow => write!(f, "Pancake underflowed its domain."),
             Error::PancakeOverflow => write!(f, "Pancake overflowed its domain."),
             Error::Io(io) => io.fmt(f),
@@ -435,3 +1280,76 @@ impl From<io::Error> for Error {
         Error::Io(error)
     }
 }
+
+/// An [`Error`](./enum.Error.html) together with the state of the machine when it occurred.
+///
+/// As failing commands leave the stack untouched, the stack is the one the failing command was executed on.
+/// ```rust
+/// use pancakestack::{Command, Machine};
+///
+/// let program = [
+///     Command::PutThisPancakeOnTop("good".into()),
+///     Command::TakeFromTheTopPancakes,
+/// ];
+/// let error = Machine::new().run(&program, std::io::empty(), std::io::sink()).unwrap_err();
+/// assert_eq!(error.program_counter(), 1);
+/// assert_eq!(error.command(), &Command::TakeFromTheTopPancakes);
+/// assert_eq!(error.stack(), &[4]);
+/// ```
+#[derive(Debug)]
+pub struct RuntimeError {
+    error: Error,
+    program_counter: usize,
+    command: Command<'static>,
+    stack: Vec<u32>,
+}
+impl RuntimeError {
+    /// Returns the error that occurred.
+    #[must_use]
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Consumes this and returns the error that occurred.
+    #[must_use]
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+
+    /// Returns the index of the failing command.
+    #[must_use]
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    /// Returns the failing command.
+    #[must_use]
+    pub fn command(&self) -> &Command<'static> {
+        &self.command
+    }
+
+    /// Returns the pancake stack the failing command was executed on, the top pancake being the last element.
+    #[must_use]
+    pub fn stack(&self) -> &[u32] {
+        &self.stack
+    }
+}
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (at command {} \"{}\" with stack {:?})",
+            self.error, self.program_counter, self.command, self.stack
+        )
+    }
+}
+impl std::error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Self {
+        error.error
+    }
+}
@@ -78,6 +78,8 @@
 //!
 //! A program can be parsed from a [`str`](https://doc.rust-lang.org/std/str/) with [`pancakestack::run_program_str`](./interpret/fn.run_program_str.html). A single line (=command) can be parsed with [`Command::from_line`](./parse/enum.Command.html#method.from_line).
 //!
+//! Invalid lines are skipped, use [`pancakestack::parse_program_str_strict`](./parse/fn.parse_program_str_strict.html) to get a diagnostic for every invalid line instead.
+//!
 //! Parsed programs are slices of [`Command`](./parse/enum.Command.html)s and can be run with [`pancakestack::run_program`](./interpret/fn.run_program.html).
 //!
 //! ```rust
@@ -89,10 +91,39 @@
 //!     Command::EatAllOfThePancakes
 //! ];
 //! pancakestack::run_program(&program, std::io::stdin(), std::io::stdout()).unwrap();
+//! ```
+//!
+//!
+//! **Drive execution**
+//!
+//! All `pancakestack::run_*` methods are built on [`Machine`](./interpret/struct.Machine.html), which can also be used directly to execute a program one command at a time.
+//!
+//! ```rust
+//! use pancakestack::{Command, Machine};
+//!
+//! let program = [Command::PutThisPancakeOnTop("test".into()), Command::ShowMeAPancake];
+//! let mut machine = Machine::new();
+//! let mut output = Vec::new();
+//! machine.run(&program, &mut std::io::empty(), &mut output).unwrap();
+//! assert_eq!(output, b"\x04");
+//! ```
 //!
+//! Programs that are run many times can be compiled with [`Bytecode::compile`](./bytecode/struct.Bytecode.html#method.compile) first, which resolves labels and adjective lengths once instead of on every execution.
 
+pub mod analyze;
+pub mod brainfuck;
+pub mod bytecode;
+pub mod cfg;
+pub mod codegen;
+pub mod debug;
+pub mod diagnostic;
+pub mod generate;
 pub mod interpret;
+pub mod optimize;
 pub mod parse;
+pub mod repl;
+pub mod suggest;
+pub mod synthesize;
 
 pub use interpret::*;
 pub use parse::*;
@@ -0,0 +1,169 @@
+use pancakestack::codegen;
+use pancakestack::diagnostic::Diagnostic;
+use pancakestack::repl::Repl;
+use pancakestack::{LabelSemantics, Machine};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::process;
+
+const USAGE: &str = "\
+Usage: pancakestack [OPTIONS] <FILE>
+       pancakestack repl
+
+Runs the Pancake Stack program in FILE using stdin as input and stdout as output.
+The repl subcommand starts an interactive session executing each entered command.
+
+Options:
+  -i, --input <FILE>  Read the input of the program from FILE instead of stdin
+      --strict        Reject the program if any line is not a valid command
+      --lenient       Skip lines that are not valid commands (default)
+      --print-stack   Print the pancake stack to stderr when the program ends
+      --labels <MODE> Jump to the command given by the top pancake when the label was declared (pancake, default)
+                      or to the command after the label (next)
+      --emit <LANG>   Print the program translated to LANG (rust, c, wat) instead of running it,
+                      only with --labels pancake
+  -h, --help          Print this help
+  -V, --version       Print the version";
+
+/// Exit code for invalid command line arguments.
+const EXIT_USAGE: i32 = 2;
+/// Exit code for programs that could not be read or parsed.
+const EXIT_INVALID_PROGRAM: i32 = 3;
+/// Exit code for input files that could not be opened.
+const EXIT_INVALID_INPUT: i32 = 4;
+
+#[derive(Debug, Default)]
+struct Options {
+    program: String,
+    input: Option<String>,
+    strict: bool,
+    print_stack: bool,
+    label_semantics: LabelSemantics,
+    emit: Option<String>,
+}
+
+fn main() {
+    if std::env::args().nth(1).as_deref() == Some("repl") {
+        if let Err(error) = Repl::new().run(io::stdin().lock(), io::stdout()) {
+            eprintln!("error: {error}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}\n\n{USAGE}");
+            process::exit(EXIT_USAGE);
+        }
+    };
+    process::exit(run(&options));
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut program = None;
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-i" | "--input" => {
+                let file = args.next().ok_or(format!("missing file for {arg}"))?;
+                options.input = Some(file);
+            }
+            "--strict" => options.strict = true,
+            "--lenient" => options.strict = false,
+            "--print-stack" => options.print_stack = true,
+            "--labels" => {
+                let mode = args.next().ok_or(format!("missing mode for {arg}"))?;
+                options.label_semantics = match mode.as_str() {
+                    "pancake" => LabelSemantics::TopPancake,
+                    "next" => LabelSemantics::NextCommand,
+                    _ => return Err(format!("unknown label mode {mode}")),
+                };
+            }
+            "--emit" => {
+                let language = args.next().ok_or(format!("missing language for {arg}"))?;
+                if !["rust", "c", "wat"].contains(&language.as_str()) {
+                    return Err(format!("unknown language {language}"));
+                }
+                options.emit = Some(language);
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                process::exit(0);
+            }
+            "-V" | "--version" => {
+                println!("pancakestack {}", env!("CARGO_PKG_VERSION"));
+                process::exit(0);
+            }
+            _ if arg.starts_with('-') => {
+                return Err(format!("unknown option {arg}"));
+            }
+            _ if program.is_none() => program = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+    if options.emit.is_some() && options.label_semantics != LabelSemantics::TopPancake {
+        return Err("--emit only supports --labels pancake".to_string());
+    }
+    options.program = program.ok_or("missing program file")?;
+    Ok(options)
+}
+
+fn run(options: &Options) -> i32 {
+    let source = match fs::read_to_string(&options.program) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("could not read {}: {error}", options.program);
+            return EXIT_INVALID_PROGRAM;
+        }
+    };
+    if options.strict {
+        if let Err(diagnostics) = pancakestack::parse_program_str_strict(&source) {
+            for diagnostic in diagnostics {
+                let diagnostic = Diagnostic::from_parse_diagnostic(&diagnostic);
+                eprintln!("{}", diagnostic.render(&options.program, &source));
+            }
+            return EXIT_INVALID_PROGRAM;
+        }
+    }
+    if let Some(language) = &options.emit {
+        let program = pancakestack::parse_program_str(&source);
+        match language.as_str() {
+            "rust" => print!("{}", codegen::rust::generate(&program)),
+            "c" => print!("{}", codegen::c::generate(&program)),
+            _ => print!("{}", codegen::wat::generate(&program)),
+        }
+        return 0;
+    }
+    let program = pancakestack::parse_program_str_spanned(&source);
+
+    let input: Box<dyn Read> = match &options.input {
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(file),
+            Err(error) => {
+                eprintln!("could not open {path}: {error}");
+                return EXIT_INVALID_INPUT;
+            }
+        },
+        None => Box::new(io::stdin()),
+    };
+    let mut output = io::stdout().lock();
+
+    let mut machine = Machine::with_label_semantics(options.label_semantics);
+    let result = machine.run_spanned(&program, input, &mut output);
+    let _ = output.flush();
+
+    if options.print_stack {
+        eprintln!("stack: {:?}", machine.stack());
+    }
+    match result {
+        Ok(()) => 0,
+        Err(error) => {
+            let diagnostic = Diagnostic::from_runtime_error(&error, &source);
+            eprintln!("{}", diagnostic.render(&options.program, &source));
+            codegen::exit_code(error.node().error())
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::bytecode::{Bytecode, Op};
+use crate::parse::Command;
+use crate::synthesize::Synthesizer;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A sequence of commands replaced by at most one op.
+struct Group {
+    start: usize,
+    end: usize,
+    op: Option<Op>,
+}
+
+/// Compiles the given program to [`Bytecode`](../bytecode/struct.Bytecode.html) and fuses sequences of ops that can be executed at once.
+///
+/// Runs of increments and decrements are fused to [`AddToTop`](../bytecode/enum.Op.html#variant.AddToTop),
+/// runs of pops to [`Drop`](../bytecode/enum.Op.html#variant.Drop), pushes followed by pops are removed and
+/// pairs of ops that only check the stack, like flipping the pancakes twice, are replaced by [`RequireDepth`](../bytecode/enum.Op.html#variant.RequireDepth).
+///
+/// The optimized program produces the same output and errors as the original one.
+/// As labels can point to any command, jumps into the middle of a fused sequence continue with the rest of the sequence.
+/// ```rust
+/// use pancakestack::bytecode::Op;
+///
+/// let program = pancakestack::parse_program_str("\
+/// Put this good pancake on top!
+/// Put butter on the pancakes!
+/// Put butter on the pancakes!
+/// Put this test pancake on top!
+/// Eat the pancake on top!
+/// Show me a pancake!");
+/// let bytecode = pancakestack::optimize::optimize(&program);
+/// assert_eq!(bytecode.ops()[..2], [Op::Push(6), Op::Print]);
+/// ```
+#[must_use]
+pub fn optimize(program: &[Command<'_>]) -> Bytecode {
+    let bytecode = Bytecode::compile(program);
+    let groups = peephole(&bytecode.ops);
+
+    let mut ops = Vec::new();
+    let mut entries = vec![0; bytecode.ops.len()];
+    let mut ends = Vec::with_capacity(groups.len());
+    for group in &groups {
+        entries[group.start] = ops.len();
+        ops.extend(group.op);
+        ends.push(ops.len());
+    }
+
+    // jumps into the middle of a group continue with the rest of its commands before returning to the op after it
+    let mut trampolines = Vec::new();
+    for (group, &end) in groups.iter().zip(&ends) {
+        for entry in group.start + 1..group.end {
+            let rest = peephole(&bytecode.ops[entry..group.end])
+                .into_iter()
+                .filter_map(|group| group.op)
+                .collect::<Vec<_>>();
+            trampolines.push((entry, rest, end));
+        }
+    }
+    if trampolines.iter().any(|(_, rest, _)| !rest.is_empty()) {
+        ops.push(Op::Halt);
+    }
+    for (entry, rest, end) in trampolines {
+        if rest.is_empty() {
+            entries[entry] = end;
+        } else {
+            entries[entry] = ops.len();
+            ops.extend(rest);
+            ops.push(Op::Goto(end));
+        }
+    }
+
+    Bytecode {
+        ops,
+        labels: bytecode.labels,
+        entries,
+    }
+}
+
+/// Rewrites sequences of commands that only build numbers out of adjectives with the shortest known commands building the same numbers,
+/// see [`Synthesizer`](../synthesize/struct.Synthesizer.html).
+///
+/// A sequence is only rewritten if it gets shorter, sequences failing with an error are kept.
+///
+/// Labels point to positions of commands, which move when a sequence before them gets shorter.
+/// So programs containing a [`Label`](../parse/enum.Command.html#variant.Label), which includes every program with a loop, are returned unchanged.
+/// [`optimize`](./fn.optimize.html) speeds up these programs without changing the positions of their commands.
+/// ```rust
+/// let program = pancakestack::parse_program_str("\
+/// Put this delicious pancake on top!
+/// Put this delicious pancake on top!
+/// Put this delicious pancake on top!
+/// Put this delicious pancake on top!
+/// Put the top pancakes together!
+/// Put the top pancakes together!
+/// Put the top pancakes together!
+/// Show me a pancake!");
+/// let optimized = pancakestack::optimize::optimize_constants(&program);
+/// assert_eq!(optimized.len(), 6);
+///
+/// let mut with_label = program.clone();
+/// with_label.push(pancakestack::Command::Label("End".into()));
+/// assert_eq!(pancakestack::optimize::optimize_constants(&with_label), with_label);
+/// ```
+#[must_use]
+pub fn optimize_constants<'a>(program: &[Command<'a>]) -> Vec<Command<'a>> {
+    if program
+        .iter()
+        .any(|command| matches!(command, Command::Label(_)))
+    {
+        return program.to_vec();
+    }
+
+    let mut synthesizer = Synthesizer::new();
+    let mut optimized = Vec::with_capacity(program.len());
+    let mut start = 0;
+    let mut constants = Vec::new();
+    for (index, command) in program.iter().enumerate() {
+        if fold(command, &mut constants) {
+            continue;
+        }
+        let sequence = &program[start..index];
+        rewrite(sequence, &constants, &mut synthesizer, &mut optimized);
+        optimized.push(command.clone());
+        constants.clear();
+        start = index + 1;
+    }
+    rewrite(
+        &program[start..],
+        &constants,
+        &mut synthesizer,
+        &mut optimized,
+    );
+    optimized
+}
+
+/// Applies the command to the numbers built by the current sequence, returns false if it needs other pancakes or fails.
+fn fold(command: &Command<'_>, constants: &mut Vec<u32>) -> bool {
+    let result = match (command, constants.as_slice()) {
+        (Command::PutThisPancakeOnTop(adjective), _) => {
+            constants.push(adjective.graphemes(true).count() as u32);
+            return true;
+        }
+        (Command::PutAnotherPancakeOnTop, [.., top]) => Some(*top),
+        (Command::PutTheTopPancakesTogether, [.., second, first]) => first.checked_add(*second),
+        (Command::TakeFromTheTopPancakes, [.., second, first]) => first.checked_sub(*second),
+        (Command::PutButterOnThePancakes, [.., top]) => top.checked_add(1),
+        (Command::TakeOffTheButter, [.., top]) => top.checked_sub(1),
+        (Command::FlipThePancakesOnTop, [.., _, _]) => {
+            let len = constants.len();
+            constants.swap(len - 1, len - 2);
+            return true;
+        }
+        (Command::EatThePancakeOnTop, [.., _]) => {
+            constants.pop();
+            return true;
+        }
+        _ => return false,
+    };
+    let result = match result {
+        Some(result) => result,
+        None => return false,
+    };
+    match command {
+        Command::PutTheTopPancakesTogether | Command::TakeFromTheTopPancakes => {
+            constants.truncate(constants.len() - 2);
+        }
+        Command::PutButterOnThePancakes | Command::TakeOffTheButter => {
+            constants.pop();
+        }
+        _ => {}
+    }
+    constants.push(result);
+    true
+}
+
+/// Appends the commands building the given numbers if they are shorter than the sequence, the sequence otherwise.
+fn rewrite<'a>(
+    sequence: &[Command<'a>],
+    constants: &[u32],
+    synthesizer: &mut Synthesizer,
+    optimized: &mut Vec<Command<'a>>,
+) {
+    let cost = constants
+        .iter()
+        .map(|&constant| synthesizer.cost(constant))
+        .sum::<usize>();
+    if cost < sequence.len() {
+        for &constant in constants {
+            optimized.extend(synthesizer.synthesize(constant));
+        }
+    } else {
+        optimized.extend_from_slice(sequence);
+    }
+}
+
+/// Splits the given ops into groups, fusing each op with the group before it where possible.
+fn peephole(ops: &[Op]) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    for (index, &op) in ops.iter().enumerate() {
+        let mut group = Group {
+            start: index,
+            end: index + 1,
+            op: Some(op),
+        };
+        while let Some(previous) = groups.last() {
+            let fused = match (previous.op, group.op) {
+                (Some(first), Some(second)) => fuse(first, second),
+                _ => None,
+            };
+            match fused {
+                Some(op) => {
+                    group = Group {
+                        start: previous.start,
+                        end: group.end,
+                        op,
+                    };
+                    groups.pop();
+                }
+                None => break,
+            }
+        }
+        groups.push(group);
+    }
+    groups
+}
+
+/// Returns the op with the same effect as executing the given ops after each other, `Some(None)` if they have no effect at all.
+fn fuse(first: Op, second: Op) -> Option<Option<Op>> {
+    let fused = match (first, second) {
+        (Op::Push(_), Op::Pop) => None,
+        (Op::Push(value), second) if addend(second).is_some() => {
+            let result = i64::from(value) + addend(second)?;
+            if result < 0 || result > i64::from(u32::MAX) {
+                // keep the error
+                return None;
+            }
+            Some(Op::Push(result as u32))
+        }
+        (Op::Push(value), Op::RequireDepth(1)) => Some(Op::Push(value)),
+        (Op::Dup, Op::Pop) => Some(Op::RequireDepth(1)),
+        (Op::Swap, Op::Swap) => Some(Op::RequireDepth(2)),
+        (Op::RequireDepth(first), Op::RequireDepth(second)) => {
+            Some(Op::RequireDepth(first.max(second)))
+        }
+        (Op::Pop, Op::Pop) => Some(Op::Drop(2)),
+        (Op::Drop(count), Op::Pop) | (Op::Pop, Op::Drop(count)) => Some(Op::Drop(count + 1)),
+        (Op::Drop(first), Op::Drop(second)) => Some(Op::Drop(first + second)),
+        (first, second) => {
+            let (first, second) = (addend(first)?, addend(second)?);
+            // an increment after a decrement can fail even if the sum is zero
+            if (first < 0) != (second < 0) {
+                return None;
+            }
+            Some(Op::AddToTop(first + second))
+        }
+    };
+    Some(fused)
+}
+
+/// Returns the value the given op adds to the top value, if it only does that.
+fn addend(op: Op) -> Option<i64> {
+    match op {
+        Op::Increment => Some(1),
+        Op::Decrement => Some(-1),
+        Op::AddToTop(value) => Some(value),
+        _ => None,
+    }
+}
@@ -1,8 +1,10 @@
+use crate::suggest::Suggestion;
 use lazy_regex::{lazy_regex, Lazy};
 use regex::Regex;
 use std::{
     borrow::Cow,
     fmt::{self, Display},
+    ops::Range,
 };
 
 /// Parses the given str into an vec of commands.
@@ -22,13 +24,74 @@ use std::{
 /// # }
 /// ```
 #[must_use]
-pub fn parse_program_str(program: &str) -> Vec<Command> {
+pub fn parse_program_str(program: &str) -> Vec<Command<'_>> {
     program
         .lines()
         .filter_map(|line| Command::from_line(line).ok())
         .collect()
 }
 
+/// Parses the given str into an vec of commands together with their location in the given str.
+/// Each command has to be on its own line, lines that cannot be parsed are skipped like in [`parse_program_str`](./fn.parse_program_str.html).
+/// ```rust
+/// let program = pancakestack::parse_program_str_spanned("Put this test pancake on top!\n\nShow me a pancake!");
+/// assert_eq!(program[1].line(), 3);
+/// assert_eq!(program[1].span(), 31..49);
+/// ```
+#[must_use]
+pub fn parse_program_str_spanned(program: &str) -> Vec<Spanned<Command<'_>>> {
+    numbered_lines(program)
+        .filter_map(|(line_number, span, line)| {
+            Some(Spanned::new(
+                Command::from_line(line).ok()?,
+                line_number,
+                span,
+            ))
+        })
+        .collect()
+}
+
+/// Returns the 1-indexed line number, the byte range and the contents of every line in the given str.
+fn numbered_lines(program: &str) -> impl Iterator<Item = (usize, Range<usize>, &str)> {
+    program.lines().enumerate().map(move |(index, line)| {
+        let start = line.as_ptr() as usize - program.as_ptr() as usize;
+        (index + 1, start..start + line.len(), line)
+    })
+}
+
+/// Parses the given str into an vec of commands, rejecting lines that are not valid commands.
+/// Each command has to be on its own line, lines containing only whitespace are skipped.
+///
+/// # Errors
+/// Will return [`Err`] containing a [`ParseDiagnostic`](./struct.ParseDiagnostic.html) for every line that cannot be parsed as a command.
+/// ```rust
+/// let diagnostics = pancakestack::parse_program_str_strict("Put this pancake on top\n\nShow me a pancake!\nShow me the pancake!").unwrap_err();
+/// assert_eq!(diagnostics.len(), 2);
+/// assert_eq!(diagnostics[0].line_number(), 1);
+/// assert_eq!(diagnostics[1].line_number(), 4);
+/// assert_eq!(diagnostics[1].line(), "Show me the pancake!");
+/// ```
+pub fn parse_program_str_strict(
+    program: &str,
+) -> Result<Vec<Command<'_>>, Vec<ParseDiagnostic<'_>>> {
+    let mut commands = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (line_number, span, line) in numbered_lines(program) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Command::from_line(line) {
+            Ok(command) => commands.push(command),
+            Err(error) => diagnostics.push(ParseDiagnostic::new(line_number, span, error)),
+        }
+    }
+    if diagnostics.is_empty() {
+        Ok(commands)
+    } else {
+        Err(diagnostics)
+    }
+}
+
 /// An enum representing a pancakestack command.
 /// Labels and pancake adjectives are stored in [`str`](https://doc.rust-lang.org/std/str/)s .
 /// See [`Command`](./enum.Command.html) for a version that uses [`String`](https://doc.rust-lang.org/std/string/struct.String.html)s.
@@ -212,10 +275,10 @@ impl Display for Command<'_> {
             Self::PutAnotherPancakeOnTop => write!(f, "Put another pancake on top!"),
             Self::Label(label) => write!(f, "[{label}]"),
             Self::IfThePancakeIsntTastyGoOverTo(label) => {
-                write!(f, "If the pancake isn't tasty, go over to \"{label}\"\\.")
+                write!(f, "If the pancake isn't tasty, go over to \"{label}\".")
             }
             Self::IfThePancakeIsTastyGoOverTo(label) => {
-                write!(f, "If the pancake is tasty, go over to \"{label}\"\\.")
+                write!(f, "If the pancake is tasty, go over to \"{label}\".")
             }
             Self::PutSyrupOnThePancakes => write!(f, "Put syrup on the pancakes!"),
             Self::PutButterOnThePancakes => write!(f, "Put butter on the pancakes!"),
@@ -226,7 +289,7 @@ impl Display for Command<'_> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct CommandParseError<'line> {
     line: &'line str,
 }
@@ -240,6 +303,28 @@ impl<'line> CommandParseError<'line> {
     pub fn line(&self) -> &str {
         self.line
     }
+
+    /// Returns the diagnostic code identifying this kind of error.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        "E0001"
+    }
+
+    /// Returns the command that was most likely meant by the line, if any command is close enough.
+    /// See [`suggest`](../suggest/fn.suggest.html).
+    /// ```rust
+    /// use pancakestack::Command;
+    ///
+    /// let error = Command::from_line("Show me the pancake!").unwrap_err();
+    /// assert_eq!(error.suggestion(), Some(Command::ShowMeAPancake));
+    /// ```
+    #[must_use]
+    pub fn suggestion(&self) -> Option<Command<'line>> {
+        crate::suggest::suggest(self.line)
+            .into_iter()
+            .next()
+            .map(Suggestion::into_command)
+    }
 }
 impl Display for CommandParseError<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -247,3 +332,104 @@ impl Display for CommandParseError<'_> {
     }
 }
 impl std::error::Error for CommandParseError<'_> {}
+
+/// A line of a program that could not be parsed as a command.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseDiagnostic<'line> {
+    line_number: usize,
+    span: Range<usize>,
+    error: CommandParseError<'line>,
+}
+impl<'line> ParseDiagnostic<'line> {
+    #[must_use]
+    pub fn new(line_number: usize, span: Range<usize>, error: CommandParseError<'line>) -> Self {
+        ParseDiagnostic {
+            line_number,
+            span,
+            error,
+        }
+    }
+
+    /// Returns the 1-indexed number of the invalid line.
+    #[must_use]
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// Returns the byte range of the invalid line in the parsed str.
+    #[must_use]
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Returns the contents of the invalid line.
+    #[must_use]
+    pub fn line(&self) -> &str {
+        self.error.line()
+    }
+
+    #[must_use]
+    pub fn error(&self) -> &CommandParseError<'line> {
+        &self.error
+    }
+}
+impl Display for ParseDiagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line_number, self.error)
+    }
+}
+impl std::error::Error for ParseDiagnostic<'_> {}
+
+/// A value together with the location in the source it originates from.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Spanned<T> {
+    node: T,
+    line: usize,
+    span: Range<usize>,
+}
+impl<T> Spanned<T> {
+    #[must_use]
+    pub fn new(node: T, line: usize, span: Range<usize>) -> Self {
+        Spanned { node, line, span }
+    }
+
+    /// Returns the located value.
+    #[must_use]
+    pub fn node(&self) -> &T {
+        &self.node
+    }
+
+    /// Consumes this and returns the located value.
+    #[must_use]
+    pub fn into_node(self) -> T {
+        self.node
+    }
+
+    /// Returns the 1-indexed line number of the value.
+    #[must_use]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the byte range of the value in the source.
+    #[must_use]
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Replaces the located value, keeping the location.
+    #[must_use]
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        Spanned::new(f(self.node), self.line, self.span)
+    }
+}
+impl<T: Display> Display for Spanned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.node)
+    }
+}
+impl<T: std::error::Error> std::error::Error for Spanned<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.node.source()
+    }
+}
@@ -0,0 +1,341 @@
+use crate::interpret::{Error, Machine};
+use crate::parse::Command;
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, prelude::*, Read, Write};
+
+/// An interactive session executing each entered command immediately against a persistent pancake stack.
+///
+/// Entered commands are remembered so that jumps back to a label replay the previously entered lines,
+/// like [`run_program_from_read`](../interpret/fn.run_program_from_read.html) does with the lines it has read.
+/// ```rust
+/// use pancakestack::repl::Repl;
+///
+/// let mut repl = Repl::new();
+/// let mut output = Vec::new();
+/// repl.eval_line("Put this old pancake on top!", std::io::empty(), &mut output).unwrap();
+/// repl.eval_line("[Loop]", std::io::empty(), &mut output).unwrap();
+/// repl.eval_line("Take off the butter!", std::io::empty(), &mut output).unwrap();
+/// assert_eq!(repl.machine().stack(), &[2]);
+///
+/// // jumps back to the third line until the pancake is not tasty anymore
+/// repl.eval_line("If the pancake is tasty, go over to \"Loop\".", std::io::empty(), &mut output).unwrap();
+/// assert_eq!(repl.machine().stack(), &[0]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Repl {
+    machine: Machine,
+    history: Vec<Command<'static>>,
+}
+
+impl Repl {
+    /// Creates a new session with an empty stack.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the machine executing the entered commands.
+    #[must_use]
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// Returns the commands entered so far.
+    #[must_use]
+    pub fn history(&self) -> &[Command<'static>] {
+        &self.history
+    }
+
+    /// Clears the stack, the labels and the entered commands.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Parses the given line and executes it. See [`execute`](#method.execute).
+    ///
+    /// # Errors
+    /// Will return `Err` if the line is not a valid command, the command performs an illegal operation or an io error occurs.
+    pub fn eval_line(
+        &mut self,
+        line: &str,
+        input: impl Read,
+        output: impl Write,
+    ) -> Result<(), ReplError> {
+        let command =
+            Command::from_line(line).map_err(|_| ReplError::InvalidCommand(line.to_string()))?;
+        self.execute(command, input, output)?;
+        Ok(())
+    }
+
+    /// Executes the given command and, if it jumps back, the previously entered commands it jumps to.
+    /// Jumps to commands that have not been entered yet continue with the next entered command.
+    ///
+    /// A command that fails is not remembered.
+    ///
+    /// # Errors
+    /// Will return `Err` if a command performs an illegal operation or an io error occurs. See [`Error`](../interpret/enum.Error.html).
+    pub fn execute(
+        &mut self,
+        command: Command<'_>,
+        mut input: impl Read,
+        mut output: impl Write,
+    ) -> Result<(), Error> {
+        let index = self.history.len();
+        self.machine.set_program_counter(index);
+        self.history.push(command.into_owned());
+
+        while !self.machine.is_halted() {
+            let command = match self.history.get(self.machine.program_counter()) {
+                Some(command) => command,
+                None => break,
+            };
+            if let Err(error) = self.machine.execute(command, &mut input, &mut output) {
+                if self.machine.program_counter() == index {
+                    self.history.pop();
+                }
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes every valid line of the given source as if it was entered. Invalid lines are skipped.
+    ///
+    /// # Errors
+    /// Will return `Err` if a command performs an illegal operation or an io error occurs. See [`Error`](../interpret/enum.Error.html).
+    pub fn load(
+        &mut self,
+        source: &str,
+        mut input: impl Read,
+        mut output: impl Write,
+    ) -> Result<(), Error> {
+        for command in source
+            .lines()
+            .filter_map(|line| Command::from_line(line).ok())
+        {
+            if self.machine.is_halted() {
+                break;
+            }
+            self.execute(command, &mut input, &mut output)?;
+        }
+        Ok(())
+    }
+
+    /// Runs an interactive session reading lines from `lines` and writing the output of the program and the stack to `console`.
+    /// Input requested by the program is read from `lines` as well.
+    ///
+    /// Besides commands the meta-commands `:stack`, `:labels`, `:history`, `:reset`, `:load <file>`, `:help` and `:quit` are supported.
+    ///
+    /// # Errors
+    /// Will return `Err` if reading lines or writing to the console fails.
+    /// Errors of the executed commands are reported on the console.
+    pub fn run(&mut self, mut lines: impl BufRead, mut console: impl Write) -> io::Result<()> {
+        let mut line = String::new();
+        loop {
+            write!(console, "> ")?;
+            console.flush()?;
+            line.clear();
+            if lines.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let trimmed = line.trim_end_matches(&['\n', '\r'][..]);
+            if trimmed.trim().is_empty() {
+                continue;
+            }
+
+            let result = match trimmed
+                .trim()
+                .split_once(' ')
+                .unwrap_or((trimmed.trim(), ""))
+            {
+                (":q", _) | (":quit", _) => return Ok(()),
+                (":stack", _) => Ok(()),
+                (":labels", _) => {
+                    self.print_labels(&mut console)?;
+                    continue;
+                }
+                (":history", _) => {
+                    for (index, command) in self.history.iter().enumerate() {
+                        writeln!(console, "{:>4} | {command}", index + 1)?;
+                    }
+                    continue;
+                }
+                (":reset", _) => {
+                    self.reset();
+                    Ok(())
+                }
+                (":load", path) => match fs::read_to_string(path.trim()) {
+                    Ok(source) => with_program_io(&mut lines, &mut console, |input, output| {
+                        self.load(&source, input, output).map_err(ReplError::from)
+                    })?,
+                    Err(error) => {
+                        writeln!(console, "could not read {}: {error}", path.trim())?;
+                        continue;
+                    }
+                },
+                (":help", _) | (":h", _) => {
+                    writeln!(console, "Enter a command to execute it. Meta-commands:")?;
+                    writeln!(console, ":stack        print the pancake stack")?;
+                    writeln!(console, ":labels       print the defined labels")?;
+                    writeln!(console, ":history      print the entered commands")?;
+                    writeln!(console, ":reset        start over with an empty stack")?;
+                    writeln!(console, ":load <file>  execute the commands of a file")?;
+                    writeln!(console, ":quit         end the session")?;
+                    continue;
+                }
+                (meta, _) if meta.starts_with(':') => {
+                    writeln!(console, "unknown meta-command \"{meta}\", try \":help\"")?;
+                    continue;
+                }
+                _ if self.machine.is_halted() => {
+                    writeln!(console, "all pancakes were eaten, use :reset to start over")?;
+                    continue;
+                }
+                _ => with_program_io(&mut lines, &mut console, |input, output| {
+                    self.eval_line(trimmed, input, output)
+                })?,
+            };
+
+            if let Err(error) = result {
+                writeln!(console, "error: {error}")?;
+            }
+            if self.machine.is_halted() {
+                writeln!(console, "all pancakes were eaten")?;
+            }
+            writeln!(console, "stack: {:?}", self.machine.stack())?;
+        }
+    }
+
+    fn print_labels(&self, mut console: impl Write) -> io::Result<()> {
+        let mut labels = self.machine.labels().iter().collect::<Vec<_>>();
+        labels.sort();
+        if labels.is_empty() {
+            return writeln!(console, "(no labels)");
+        }
+        for (label, position) in labels {
+            match self.history.get(*position) {
+                Some(command) => writeln!(console, "[{label}] -> {}: {command}", position + 1)?,
+                None => writeln!(console, "[{label}] -> {}", position + 1)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs `f` with the input of the program read from `lines` and its output written to `console`.
+/// Output that does not end the line is ended once the program is done or prompts for input.
+fn with_program_io<R: BufRead, W: Write, T>(
+    lines: &mut R,
+    console: &mut W,
+    f: impl FnOnce(PromptingInput<'_, '_, R, W>, ProgramOutput<'_, '_, W>) -> T,
+) -> io::Result<T> {
+    let console = RefCell::new(SharedConsole {
+        console,
+        output_pending: false,
+    });
+    let result = f(
+        PromptingInput {
+            lines,
+            console: &console,
+        },
+        ProgramOutput(&console),
+    );
+    console.into_inner().end_output()?;
+    Ok(result)
+}
+
+/// The console of a session, shared by the output of the program and the prompts for its input.
+struct SharedConsole<'c, W> {
+    console: &'c mut W,
+    /// Whether the program wrote output since the last prompt.
+    output_pending: bool,
+}
+
+impl<W: Write> SharedConsole<'_, W> {
+    /// Ends the line of the output written by the program, if there is any.
+    fn end_output(&mut self) -> io::Result<()> {
+        if self.output_pending {
+            self.output_pending = false;
+            writeln!(self.console)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the output of the program directly to the console of the session.
+struct ProgramOutput<'a, 'c, W>(&'a RefCell<SharedConsole<'c, W>>);
+
+impl<W: Write> Write for ProgramOutput<'_, '_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut console = self.0.borrow_mut();
+        let length = console.console.write(buf)?;
+        console.output_pending |= length > 0;
+        Ok(length)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().console.flush()
+    }
+}
+
+/// Reads input requested by the program line by line from the session, prompting for each line.
+struct PromptingInput<'a, 'c, R, W> {
+    lines: &'a mut R,
+    console: &'a RefCell<SharedConsole<'c, W>>,
+}
+
+impl<R: BufRead, W: Write> Read for PromptingInput<'_, '_, R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        {
+            let mut console = self.console.borrow_mut();
+            console.end_output()?;
+            write!(console.console, "input> ")?;
+            console.console.flush()?;
+        }
+
+        let available = self.lines.fill_buf()?;
+        if available.is_empty() {
+            return Ok(0);
+        }
+        let length = available
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(available.len(), |index| index + 1)
+            .min(buf.len());
+        buf[..length].copy_from_slice(&available[..length]);
+        self.lines.consume(length);
+        Ok(length)
+    }
+}
+
+/// An enum representing the possible errors when evaluating a line in a [`Repl`](./struct.Repl.html).
+#[derive(Debug)]
+pub enum ReplError {
+    /// The line is not a valid command.
+    InvalidCommand(String),
+    /// The command failed. See [`Error`](../interpret/enum.Error.html).
+    Execution(Error),
+}
+
+impl std::fmt::Display for ReplError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplError::InvalidCommand(line) => write!(f, "invalid command: \"{line}\""),
+            ReplError::Execution(error) => error.fmt(f),
+        }
+    }
+}
+impl std::error::Error for ReplError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReplError::InvalidCommand(_) => None,
+            ReplError::Execution(error) => Some(error),
+        }
+    }
+}
+impl From<Error> for ReplError {
+    fn from(error: Error) -> Self {
+        ReplError::Execution(error)
+    }
+}
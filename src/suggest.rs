@@ -0,0 +1,210 @@
+use crate::parse::Command;
+use std::borrow::Cow;
+
+/// A command that was likely meant by a line that could not be parsed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Suggestion<'line> {
+    command: Command<'line>,
+    distance: usize,
+}
+
+impl<'line> Suggestion<'line> {
+    /// Returns the suggested command.
+    #[must_use]
+    pub fn command(&self) -> &Command<'line> {
+        &self.command
+    }
+
+    /// Consumes this and returns the suggested command.
+    #[must_use]
+    pub fn into_command(self) -> Command<'line> {
+        self.command
+    }
+
+    /// Returns the number of characters that have to be inserted, removed or replaced to turn the line into the suggested command.
+    #[must_use]
+    pub fn distance(&self) -> usize {
+        self.distance
+    }
+}
+
+/// The shape of a command, parameterized commands are split into the text before and after their parameter.
+enum Template {
+    Fixed(Command<'static>),
+    Adjective,
+    Label,
+    IfThePancakeIsntTastyGoOverTo,
+    IfThePancakeIsTastyGoOverTo,
+}
+
+const TEMPLATES: [Template; 17] = [
+    Template::Fixed(Command::EatThePancakeOnTop),
+    Template::Fixed(Command::PutTheTopPancakesTogether),
+    Template::Fixed(Command::GiveMeAPancake),
+    Template::Fixed(Command::HowAboutAHotcake),
+    Template::Fixed(Command::ShowMeAPancake),
+    Template::Fixed(Command::TakeFromTheTopPancakes),
+    Template::Fixed(Command::FlipThePancakesOnTop),
+    Template::Fixed(Command::PutAnotherPancakeOnTop),
+    Template::Fixed(Command::PutSyrupOnThePancakes),
+    Template::Fixed(Command::PutButterOnThePancakes),
+    Template::Fixed(Command::TakeOffTheSyrup),
+    Template::Fixed(Command::TakeOffTheButter),
+    Template::Fixed(Command::EatAllOfThePancakes),
+    Template::Adjective,
+    Template::Label,
+    Template::IfThePancakeIsntTastyGoOverTo,
+    Template::IfThePancakeIsTastyGoOverTo,
+];
+
+impl Template {
+    /// Returns the text before and after the parameter of a parameterized command.
+    fn affixes(&self) -> (&'static str, &'static str) {
+        match self {
+            Template::Fixed(_) => unreachable!("fixed commands have no parameter"),
+            Template::Adjective => ("Put this ", " pancake on top!"),
+            Template::Label => ("[", "]"),
+            Template::IfThePancakeIsntTastyGoOverTo => {
+                ("If the pancake isn't tasty, go over to \"", "\".")
+            }
+            Template::IfThePancakeIsTastyGoOverTo => {
+                ("If the pancake is tasty, go over to \"", "\".")
+            }
+        }
+    }
+
+    /// Returns the command for this template closest to the given line and its distance to the line.
+    fn closest_command<'line>(&self, line: &'line str) -> Option<(Command<'line>, usize, usize)> {
+        if let Template::Fixed(command) = self {
+            let text = command.to_string();
+            let length = text.chars().count();
+            return Some((command.clone(), edit_distance(line, &text), length));
+        }
+
+        let (prefix, suffix) = self.affixes();
+        let chars = line
+            .char_indices()
+            .map(|(index, _)| index)
+            .chain(Some(line.len()))
+            .collect::<Vec<_>>();
+        let line_chars = line.chars().collect::<Vec<_>>();
+        let reversed_chars = line_chars.iter().rev().copied().collect::<Vec<_>>();
+        let before = prefix_distances(prefix.chars(), &line_chars);
+        let after = prefix_distances(suffix.chars().rev(), &reversed_chars);
+
+        // adjectives cannot contain whitespace, every whitespace character has to be replaced
+        let mut whitespace = vec![0; line_chars.len() + 1];
+        for (index, c) in line_chars.iter().enumerate() {
+            whitespace[index + 1] = whitespace[index] + usize::from(c.is_whitespace());
+        }
+
+        // the prefix cannot match if more than the allowed distance is left before the parameter, likewise for the suffix
+        let (prefix_length, suffix_length) = (prefix.chars().count(), suffix.chars().count());
+        let max_distance = max_distance(prefix_length + suffix_length);
+        let n = line_chars.len();
+        let last_start = n.min(prefix_length + max_distance);
+        let first_end = n.saturating_sub(suffix_length + max_distance);
+        let mut best: Option<(usize, usize, usize)> = None;
+        for start in 0..=last_start {
+            for end in start.max(first_end)..=n {
+                if matches!(self, Template::Label) && start == end {
+                    continue;
+                }
+                let mut distance = before[start] + after[n - end];
+                if let Template::Adjective = self {
+                    distance += whitespace[end] - whitespace[start];
+                }
+                // on ties keep as much of the line as possible in the parameter instead of replacing it
+                let better = match best {
+                    Some((best_distance, best_start, best_end)) => {
+                        (distance, best_end - best_start) < (best_distance, end - start)
+                    }
+                    None => true,
+                };
+                if better {
+                    best = Some((distance, start, end));
+                }
+            }
+        }
+        let (distance, start, end) = best?;
+
+        let parameter = &line[chars[start]..chars[end]];
+        let command = match self {
+            Template::Fixed(_) => unreachable!("fixed commands have no parameter"),
+            Template::Adjective if parameter.contains(char::is_whitespace) => {
+                Command::PutThisPancakeOnTop(Cow::Owned(
+                    parameter.replace(char::is_whitespace, "-"),
+                ))
+            }
+            Template::Adjective => Command::PutThisPancakeOnTop(parameter.into()),
+            Template::Label => Command::Label(parameter.into()),
+            Template::IfThePancakeIsntTastyGoOverTo => {
+                Command::IfThePancakeIsntTastyGoOverTo(parameter.into())
+            }
+            Template::IfThePancakeIsTastyGoOverTo => {
+                Command::IfThePancakeIsTastyGoOverTo(parameter.into())
+            }
+        };
+        Some((command, distance, prefix_length + suffix_length))
+    }
+}
+
+/// Returns the commands likely meant by the given line, the most likely first.
+///
+/// Every command template, including the parameterized ones like `Put this {adjective} pancake on top!`, is compared to the line.
+/// Parameters are taken from the line, so only typos in the fixed parts of a command count towards the distance.
+/// Only commands whose distance is small compared to the length of their fixed parts are suggested.
+/// ```rust
+/// use pancakestack::{suggest::suggest, Command};
+///
+/// let suggestions = suggest("Put this tasty pancake on the top!");
+/// assert_eq!(suggestions[0].command(), &Command::PutThisPancakeOnTop("tasty".into()));
+/// assert_eq!(suggestions[0].distance(), 4);
+/// ```
+#[must_use]
+pub fn suggest(line: &str) -> Vec<Suggestion<'_>> {
+    let mut suggestions = TEMPLATES
+        .iter()
+        .filter_map(|template| template.closest_command(line))
+        .filter(|(_, distance, length)| *distance <= max_distance(*length))
+        .map(|(command, distance, _)| Suggestion { command, distance })
+        .collect::<Vec<_>>();
+    suggestions.sort_by_key(Suggestion::distance);
+    suggestions
+}
+
+/// Returns the largest distance of a suggestion for a text of the given length, like the fixed parts of a command.
+pub(crate) fn max_distance(length: usize) -> usize {
+    (length / 3).max(1)
+}
+
+/// Returns the distances between the given pattern and every prefix of the given text.
+fn prefix_distances(pattern: impl Iterator<Item = char>, text: &[char]) -> Vec<usize> {
+    let mut previous = (0..=text.len()).collect::<Vec<_>>();
+    let mut current = vec![0; text.len() + 1];
+    for (i, p) in pattern.enumerate() {
+        current[0] = i + 1;
+        for (j, t) in text.iter().enumerate() {
+            let substitution = previous[j] + usize::from(p != *t);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous
+}
+
+/// Returns the Levenshtein distance between the given strs in chars.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,212 @@
+use crate::parse::Command;
+use std::collections::HashMap;
+
+/// Adjectives whose grapheme count is their index, the longest one is the largest number that can be pushed at once.
+const ADJECTIVES: [&str; 14] = [
+    "",
+    "½",
+    "ok",
+    "hot",
+    "rich",
+    "tasty",
+    "dainty",
+    "buttery",
+    "pleasant",
+    "delicious",
+    "delectable",
+    "scrumptious",
+    "irresistible",
+    "mouthwatering",
+];
+
+/// The largest number pushed with a single adjective.
+const LARGEST_ADJECTIVE: u32 = ADJECTIVES.len() as u32 - 1;
+
+/// The factors numbers are built with, larger factors are products of these.
+const FACTORS: [u32; 4] = [2, 3, 5, 7];
+
+/// Numbers below this are also built by adding to smaller numbers without multiplying.
+const SMALL: u32 = 1024;
+
+/// The shortest known way to build a number.
+#[derive(Debug, Clone, Copy)]
+struct Plan {
+    /// The number of commands.
+    cost: usize,
+    /// The number that is built first, the number itself if it is pushed with a single adjective.
+    base: u32,
+    /// The factor the base is multiplied with.
+    factor: u32,
+    /// The number added to the product afterwards.
+    addend: i64,
+}
+
+/// Finds short sequences of commands putting a number on top of the stack.
+///
+/// A number is built by pushing an adjective, multiplying it with [`PutAnotherPancakeOnTop`](../parse/enum.Command.html#variant.PutAnotherPancakeOnTop)
+/// and [`PutTheTopPancakesTogether`](../parse/enum.Command.html#variant.PutTheTopPancakesTogether) and adding or subtracting small numbers, recursively.
+/// The pancakes below the new one are never touched, so the commands can be inserted anywhere.
+///
+/// The synthesizer remembers every number it has built, so building many numbers with one synthesizer is faster than using [`synthesize`](./fn.synthesize.html) for each.
+#[derive(Debug, Default)]
+pub struct Synthesizer {
+    plans: HashMap<u32, Plan>,
+}
+
+impl Synthesizer {
+    /// Creates a new synthesizer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of commands of [`synthesize`](#method.synthesize) for the given number.
+    pub fn cost(&mut self, target: u32) -> usize {
+        self.plan(target).cost
+    }
+
+    /// Returns the shortest known sequence of commands putting the given number on top of the stack.
+    /// ```rust
+    /// use pancakestack::synthesize::Synthesizer;
+    ///
+    /// let mut synthesizer = Synthesizer::new();
+    /// let commands = synthesizer.synthesize(1_000_000);
+    /// assert!(commands.len() < 40);
+    ///
+    /// let mut machine = pancakestack::Machine::new();
+    /// machine.run(&commands, &mut std::io::empty(), &mut std::io::sink()).unwrap();
+    /// assert_eq!(machine.stack(), &[1_000_000]);
+    /// ```
+    pub fn synthesize(&mut self, target: u32) -> Vec<Command<'static>> {
+        let mut commands = Vec::with_capacity(self.cost(target));
+        self.build(target, &mut commands);
+        commands
+    }
+
+    fn build(&mut self, target: u32, commands: &mut Vec<Command<'static>>) {
+        let plan = self.plan(target);
+        if plan.factor == 0 {
+            commands.push(push(plan.base));
+            return;
+        }
+        self.build(plan.base, commands);
+        multiply(plan.factor, commands);
+        if plan.addend == 1 {
+            commands.push(Command::PutButterOnThePancakes);
+        } else if plan.addend == -1 {
+            commands.push(Command::TakeOffTheButter);
+        } else if plan.addend > 0 {
+            commands.push(push(plan.addend as u32));
+            commands.push(Command::PutTheTopPancakesTogether);
+        } else if plan.addend < 0 {
+            commands.push(push(-plan.addend as u32));
+            commands.push(Command::FlipThePancakesOnTop);
+            commands.push(Command::TakeFromTheTopPancakes);
+        }
+    }
+
+    fn plan(&mut self, target: u32) -> Plan {
+        if target <= LARGEST_ADJECTIVE {
+            return Plan {
+                cost: 1,
+                base: target,
+                factor: 0,
+                addend: 0,
+            };
+        }
+        if let Some(plan) = self.plans.get(&target) {
+            return *plan;
+        }
+
+        let mut best: Option<Plan> = None;
+        let largest = i64::from(LARGEST_ADJECTIVE);
+        for addend in -largest..=largest {
+            let rest = i64::from(target) - addend;
+            if rest > i64::from(u32::MAX) {
+                continue;
+            }
+            let factors: &[u32] = if addend > 0 && target < SMALL {
+                &[1]
+            } else {
+                &[]
+            };
+            for &factor in factors.iter().chain(&FACTORS) {
+                let factor_i64 = i64::from(factor);
+                if rest < factor_i64 || rest % factor_i64 != 0 {
+                    continue;
+                }
+                let base = (rest / factor_i64) as u32;
+                let cost = multiply_cost(factor) + addend_cost(addend);
+                if best.is_some_and(|best| cost + 1 >= best.cost) {
+                    // the base takes at least one command
+                    continue;
+                }
+                let cost = cost + self.plan(base).cost;
+                if best.is_some_and(|best| cost >= best.cost) {
+                    continue;
+                }
+                best = Some(Plan {
+                    cost,
+                    base,
+                    factor,
+                    addend,
+                });
+            }
+        }
+        let best = best.expect("every number can be built from a smaller one");
+        self.plans.insert(target, best);
+        best
+    }
+}
+
+/// Returns the shortest known sequence of commands putting the given number on top of the stack.
+/// See [`Synthesizer`](./struct.Synthesizer.html).
+/// ```rust
+/// let commands = pancakestack::synthesize::synthesize(u32::MAX);
+/// let mut machine = pancakestack::Machine::new();
+/// machine.run(&commands, &mut std::io::empty(), &mut std::io::sink()).unwrap();
+/// assert_eq!(machine.stack(), &[u32::MAX]);
+/// ```
+#[must_use]
+pub fn synthesize(target: u32) -> Vec<Command<'static>> {
+    Synthesizer::new().synthesize(target)
+}
+
+fn push(value: u32) -> Command<'static> {
+    Command::PutThisPancakeOnTop(ADJECTIVES[value as usize].into())
+}
+
+/// Appends the commands multiplying the top pancake with the given factor.
+fn multiply(factor: u32, commands: &mut Vec<Command<'static>>) {
+    match factor {
+        0 | 1 => {}
+        factor if factor % 2 == 0 => {
+            multiply(factor / 2, commands);
+            commands.push(Command::PutAnotherPancakeOnTop);
+            commands.push(Command::PutTheTopPancakesTogether);
+        }
+        factor => {
+            // keep a copy of the pancake below and add it to the product with the factor minus one
+            commands.push(Command::PutAnotherPancakeOnTop);
+            multiply(factor - 1, commands);
+            commands.push(Command::PutTheTopPancakesTogether);
+        }
+    }
+}
+
+fn multiply_cost(factor: u32) -> usize {
+    match factor {
+        0 | 1 => 0,
+        factor if factor % 2 == 0 => multiply_cost(factor / 2) + 2,
+        factor => multiply_cost(factor - 1) + 2,
+    }
+}
+
+fn addend_cost(addend: i64) -> usize {
+    match addend {
+        0 => 0,
+        1 | -1 => 1,
+        addend if addend > 0 => 2,
+        _ => 3,
+    }
+}
@@ -0,0 +1,158 @@
+use pancakestack::analyze::{analyze_stack, lint, Lint};
+use pancakestack::LabelSemantics;
+use std::fs;
+
+fn lint_str(source: &str) -> Vec<Lint> {
+    lint(
+        &pancakestack::parse_program_str(source),
+        LabelSemantics::TopPancake,
+    )
+}
+
+#[test]
+fn examples() {
+    for (example, expected) in &[
+        ("cat", vec![]),
+        ("hello_world", vec![]),
+        ("countdown_bin", vec![]),
+        ("long", vec![]),
+        ("div10", vec![Lint::UnreachableCode { commands: 40..41 }]),
+    ] {
+        let source = fs::read_to_string(format!("examples/{example}.pancake")).unwrap();
+        assert_eq!(&lint_str(&source), expected, "{example}");
+    }
+}
+
+#[test]
+fn labels() {
+    let lints = lint_str(
+        "Put this tasty pancake on top!\n[Loop]\n[Loop]\n[Unused]\nIf the pancake is tasty, go over to \"Loop\".\nIf the pancake is tasty, go over to \"Missing\".",
+    );
+    assert_eq!(
+        lints,
+        [
+            Lint::DuplicateLabel {
+                command: 2,
+                first: 1,
+                label: "Loop".to_string()
+            },
+            Lint::UnusedLabel {
+                command: 3,
+                label: "Unused".to_string()
+            },
+            Lint::UndefinedLabel {
+                command: 5,
+                label: "Missing".to_string()
+            },
+        ]
+    );
+    assert_eq!(lints[0].code(), "W0002");
+    assert_eq!(
+        lints[0].to_string(),
+        "label \"Loop\" is already declared at command 1"
+    );
+}
+
+#[test]
+fn unreachable_code() {
+    // the jump leads to the command after the first halt
+    let program = "Put this tasty pancake on top!\n[Start]\nIf the pancake is tasty, go over to \"Start\".\nEat all of the pancakes!\nShow me a pancake!\nEat all of the pancakes!\nShow me a pancake!\nShow me a pancake!";
+    let lints = lint_str(program);
+    assert_eq!(lints, [Lint::UnreachableCode { commands: 6..8 }]);
+    assert_eq!(lints[0].to_string(), "commands 6 to 7 can never run");
+
+    // the position of the label depends on the input
+    let program = "Give me a pancake!\n[Start]\nIf the pancake is tasty, go over to \"Start\".\nEat all of the pancakes!\nShow me a pancake!";
+    assert_eq!(lint_str(program), []);
+
+    // a jump leads between the commands building the position of a label
+    let program = "Put this hot pancake on top!\n[Start]\nPut this ok pancake on top!\nPut this ok pancake on top!\n[Loop]\nIf the pancake is tasty, go over to \"Start\".\nIf the pancake is tasty, go over to \"Loop\".\nEat all of the pancakes!\nShow me a pancake!";
+    assert_eq!(lint_str(program), []);
+}
+
+#[test]
+fn unreachable_code_with_labels_pointing_to_next_command() {
+    // the position of the label depends on the input, unless labels point to the command after them
+    let program = pancakestack::parse_program_str("Give me a pancake!\n[Start]\nIf the pancake is tasty, go over to \"Start\".\nEat all of the pancakes!\nShow me a pancake!");
+    assert_eq!(lint(&program, LabelSemantics::TopPancake), []);
+    assert_eq!(
+        lint(&program, LabelSemantics::NextCommand),
+        [Lint::UnreachableCode { commands: 4..5 }]
+    );
+}
+
+#[test]
+fn stack_depths_of_examples() {
+    for example in &["cat", "hello_world", "countdown_bin", "long", "div10"] {
+        let source = fs::read_to_string(format!("examples/{example}.pancake")).unwrap();
+        let analysis = analyze_stack(
+            &pancakestack::parse_program_str(&source),
+            LabelSemantics::TopPancake,
+        );
+        assert_eq!(analysis.problems(), [], "{example}");
+    }
+}
+
+#[test]
+fn stack_problem_in_loop() {
+    let program = pancakestack::parse_program_str(
+        "Put this ok pancake on top!\nPut this ok pancake on top!\nPut this tasty pancake on top!\n[Loop]\nEat the pancake on top!\nIf the pancake is tasty, go over to \"Loop\".\nEat all of the pancakes!",
+    );
+    let analysis = analyze_stack(&program, LabelSemantics::TopPancake);
+    assert_eq!(analysis.depth(3).unwrap().max(), Some(3));
+    assert_eq!(analysis.depth(4).unwrap().min(), 1);
+    assert_eq!(analysis.problems().len(), 1);
+    let problem = &analysis.problems()[0];
+    assert_eq!(problem.command(), 5);
+    assert!(!problem.always_fails());
+    assert_eq!(problem.path(), [0, 1, 2, 3, 4, 5, 4, 5, 4, 5]);
+    assert_eq!(
+        problem.to_string(),
+        "command 5 needs a pancake but there may be only 0"
+    );
+}
+
+#[test]
+fn unbounded_stack() {
+    let program = pancakestack::parse_program_str(
+        "Put this ok pancake on top!\n[Loop]\nPut another pancake on top!\nIf the pancake is tasty, go over to \"Loop\".",
+    );
+    let analysis = analyze_stack(&program, LabelSemantics::TopPancake);
+    assert_eq!(analysis.depth(2).unwrap().max(), None);
+    assert_eq!(analysis.problems(), []);
+}
+
+#[test]
+fn unknown_label_positions() {
+    // the loop can jump anywhere, including the command flipping the pancakes
+    let program = pancakestack::parse_program_str(
+        "Give me a pancake!\nPut another pancake on top!\n[Loop]\nEat the pancake on top!\nIf the pancake is tasty, go over to \"Loop\".\nFlip the pancakes on top!",
+    );
+    let analysis = analyze_stack(&program, LabelSemantics::TopPancake);
+    let commands = analysis
+        .problems()
+        .iter()
+        .map(|problem| problem.command())
+        .collect::<Vec<_>>();
+    assert_eq!(commands, [4, 5]);
+    // jumping back to the start grows the stack without bound
+    assert_eq!(analysis.problems()[1].depth().max(), None);
+}
+
+#[test]
+fn labels_pointing_to_next_command_need_no_pancake() -> Result<(), Box<dyn std::error::Error>> {
+    let program =
+        pancakestack::parse_program_str("[Start]\nPut this ok pancake on top!\nShow me a pancake!");
+    let analysis = analyze_stack(&program, LabelSemantics::NextCommand);
+    assert_eq!(analysis.problems(), []);
+    pancakestack::Machine::with_label_semantics(LabelSemantics::NextCommand).run(
+        &program,
+        std::io::empty(),
+        std::io::sink(),
+    )?;
+
+    let analysis = analyze_stack(&program, LabelSemantics::TopPancake);
+    assert_eq!(analysis.problems()[0].command(), 0);
+    assert!(analysis.problems()[0].always_fails());
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
 use std::str;
 
@@ -111,3 +111,48 @@ fn cat_program_read() -> Result<(), Box<dyn Error>> {
     assert_eq!(output, &input[..]);
     Ok(())
 }
+
+#[test]
+fn parse_program_strict() -> Result<(), Box<dyn Error>> {
+    let program_str = "Put this test pancake on top!\n\nShow me a pancake!\n";
+    let program_parsed = pancakestack::parse_program_str_strict(program_str).unwrap();
+    assert_eq!(program_parsed, pancakestack::parse_program_str(program_str));
+
+    let program_str = "Put this test pancake on top!\nPut this pancake on top\nShow me a pancake!\nEat all the pancakes!";
+    let diagnostics = pancakestack::parse_program_str_strict(program_str).unwrap_err();
+    let lines = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.line_number(), diagnostic.line()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        vec![(2, "Put this pancake on top"), (4, "Eat all the pancakes!")]
+    );
+    Ok(())
+}
+
+#[test]
+fn runtime_error_location() -> Result<(), Box<dyn Error>> {
+    let program_str = "Put this test pancake on top!\ninvalid\nEat the pancake on top!\n\n[Loop]\n";
+    let program = pancakestack::parse_program_str_spanned(program_str);
+    assert_eq!(program.len(), 3);
+    assert_eq!(&program_str[program[1].span()], "Eat the pancake on top!");
+
+    let error =
+        pancakestack::run_program_spanned(&program, std::io::empty(), std::io::sink()).unwrap_err();
+    assert!(matches!(
+        error.node().error(),
+        pancakestack::Error::OutOfPancakes
+    ));
+    assert_eq!(error.line(), 5);
+    assert_eq!(&program_str[error.span()], "[Loop]");
+    Ok(())
+}
+
+#[test]
+fn display_round_trip() {
+    let program_str = fs::read_to_string("examples/div10.pancake").unwrap();
+    for command in pancakestack::parse_program_str(&program_str) {
+        assert_eq!(Command::from_line(&command.to_string()).unwrap(), command);
+    }
+}
@@ -0,0 +1,104 @@
+use pancakestack::brainfuck::{self, BrainfuckError};
+use pancakestack::Error;
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+const CAT: &str = ",[.,]";
+const REVERSE: &str = ">,[>,]<[.<]";
+const ADD: &str = ",>,[<+>-]<------------------------------------------------.";
+const DIGITS: &str = "++++++++[>++++++<-]>[<+>-]++++++++++[<.+>-]";
+const WRAP: &str = "-.+.--[----->+<]>.";
+
+/// Runs the Brainfuck program directly, decoding the output bytes as chars.
+fn run_brainfuck(source: &str, input: &[u8]) -> String {
+    let source = source.as_bytes();
+    let mut tape = vec![0_u8; brainfuck::DEFAULT_TAPE_LENGTH];
+    let mut head = 0;
+    let mut input = input.iter();
+    let mut output = String::new();
+    let mut position = 0;
+    while position < source.len() {
+        match source[position] {
+            b'+' => tape[head] = tape[head].wrapping_add(1),
+            b'-' => tape[head] = tape[head].wrapping_sub(1),
+            b'>' => head += 1,
+            b'<' => head -= 1,
+            b'.' => output.push(char::from(tape[head])),
+            b',' => tape[head] = input.next().copied().unwrap_or(0),
+            b'[' if tape[head] == 0 => position = matching(source, position, 1),
+            b']' if tape[head] != 0 => position = matching(source, position, -1),
+            _ => {}
+        }
+        position += 1;
+    }
+    output
+}
+
+/// Returns the position of the bracket matching the one at the given position.
+fn matching(source: &[u8], mut position: usize, direction: isize) -> usize {
+    let mut depth = 0;
+    loop {
+        match source[position] {
+            b'[' => depth += direction,
+            b']' => depth -= direction,
+            _ => {}
+        }
+        if depth == 0 {
+            return position;
+        }
+        position = (position as isize + direction) as usize;
+    }
+}
+
+fn run_compiled(source: &str, input: &[u8]) -> Result<String, Error> {
+    let program = brainfuck::compile(source).unwrap();
+    let mut output = Vec::new();
+    pancakestack::run_program(&program, input, &mut output)?;
+    Ok(String::from_utf8(output).unwrap())
+}
+
+fn assert_same_output(source: &str, input: &[u8]) {
+    assert_eq!(
+        run_compiled(source, input).unwrap(),
+        run_brainfuck(source, input)
+    );
+}
+
+#[test]
+fn hello_world() {
+    assert_eq!(run_compiled(HELLO_WORLD, b"").unwrap(), "Hello World!\n");
+    assert_same_output(HELLO_WORLD, b"");
+}
+
+#[test]
+fn classic_programs() {
+    assert_same_output(CAT, b"pancakes");
+    assert_same_output(REVERSE, b"stack");
+    assert_same_output(ADD, b"34");
+    assert_same_output(DIGITS, b"");
+    assert_same_output(WRAP, b"");
+}
+
+#[test]
+fn tape_bounds() {
+    assert!(matches!(
+        run_compiled("<", b""),
+        Err(Error::PancakeUnderflow)
+    ));
+    let program = brainfuck::compile_with_tape_length(">>+.>", 3).unwrap();
+    let mut output = Vec::new();
+    let result = pancakestack::run_program(&program, &b""[..], &mut output);
+    assert!(matches!(result, Err(Error::PancakeUnderflow)));
+    assert_eq!(output, b"\x01");
+}
+
+#[test]
+fn unmatched_brackets() {
+    assert_eq!(
+        brainfuck::compile("+[[-]"),
+        Err(BrainfuckError::UnmatchedOpeningBracket(1))
+    );
+    assert_eq!(
+        brainfuck::compile("+]"),
+        Err(BrainfuckError::UnmatchedClosingBracket(1))
+    );
+}
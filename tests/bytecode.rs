@@ -0,0 +1,76 @@
+use pancakestack::bytecode::{Bytecode, Op};
+use pancakestack::Command;
+use std::error::Error;
+use std::fs;
+
+/// Runs the program with the interpreter and the bytecode vm and checks that both behave the same.
+fn assert_same_behavior(program: &[Command<'_>], input: &[u8]) {
+    let mut expected_output = Vec::new();
+    let expected = pancakestack::run_program(program, input, &mut expected_output);
+    let mut output = Vec::new();
+    let result = Bytecode::compile(program).run(input, &mut output);
+
+    assert_eq!(output, expected_output);
+    assert_eq!(
+        result.map_err(|error| error.to_string()),
+        expected.map_err(|error| error.to_string())
+    );
+}
+
+#[test]
+fn compile() {
+    let program = pancakestack::parse_program_str(
+        "Put this tasty pancake on top!\n[Start]\nTake off the butter!\nIf the pancake is tasty, go over to \"Start\".\nIf the pancake isn't tasty, go over to \"End\".\n[End]\nEat all of the pancakes!",
+    );
+    let bytecode = Bytecode::compile(&program);
+    assert_eq!(
+        bytecode.ops(),
+        &[
+            Op::Push(5),
+            Op::Label(0),
+            Op::Decrement,
+            Op::Jnz(0),
+            Op::Jz(1),
+            Op::Label(1),
+            Op::Halt,
+        ]
+    );
+    assert_eq!(bytecode.labels(), &["Start", "End"]);
+}
+
+#[test]
+fn examples() -> Result<(), Box<dyn Error>> {
+    let examples: &[(&str, &[u8])] = &[
+        ("examples/hello_world.pancake", b""),
+        ("examples/countdown_bin.pancake", &[100]),
+        ("examples/cat.pancake", b"cat\x00"),
+        ("examples/div10.pancake", b"87"),
+        ("examples/long.pancake", b""),
+    ];
+    for (path, input) in examples {
+        let source = fs::read_to_string(path)?;
+        assert_same_behavior(&pancakestack::parse_program_str(&source), input);
+    }
+    Ok(())
+}
+
+#[test]
+fn errors() {
+    let programs = [
+        "Eat the pancake on top!",
+        "Put this good pancake on top!\nPut the top pancakes together!",
+        "Put this a pancake on top!\nPut this good pancake on top!\nTake from the top pancakes!",
+        "Give me a pancake!",
+        "Put this pancake on top!\nIf the pancake isn't tasty, go over to \"Nowhere\".",
+        "Put this pancake on top!\nTake off the syrup!",
+        "Put this  pancake on top!\n[Nowhere]",
+        "Put this good pancake on top!\nShow me a pancake!\nHow about a hotcake?\nShow me a pancake!",
+    ];
+    for program in &programs {
+        assert_same_behavior(&pancakestack::parse_program_str(program), b"x");
+    }
+    assert_same_behavior(
+        &pancakestack::parse_program_str("Give me a pancake!\nGive me a pancake!"),
+        b"12\r\nnot a number\n",
+    );
+}
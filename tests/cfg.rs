@@ -0,0 +1,85 @@
+use pancakestack::cfg::{ControlFlowGraph, EdgeKind, Target};
+use pancakestack::LabelSemantics;
+use std::fs;
+
+#[test]
+fn blocks_cover_program() {
+    for example in &["cat", "hello_world", "countdown_bin", "long", "div10"] {
+        let source = fs::read_to_string(format!("examples/{example}.pancake")).unwrap();
+        let program = pancakestack::parse_program_str(&source);
+        let graph = ControlFlowGraph::new(&program, LabelSemantics::TopPancake);
+        let mut next = 0;
+        for (index, block) in graph.blocks().iter().enumerate() {
+            assert_eq!(block.commands().start, next, "{example}");
+            assert_ne!(block.commands().len(), 0, "{example}");
+            for command in block.commands() {
+                assert_eq!(graph.block_of(command), Some(index));
+            }
+            next = block.commands().end;
+        }
+        assert_eq!(next, program.len(), "{example}");
+        assert!(graph.edges().iter().all(|edge| !edge.is_dynamic()));
+    }
+}
+
+#[test]
+fn jumps_split_blocks() {
+    let source = fs::read_to_string("examples/div10.pancake").unwrap();
+    let program = pancakestack::parse_program_str(&source);
+    let graph = ControlFlowGraph::new(&program, LabelSemantics::TopPancake);
+    let loop_block = graph.block_of(12).unwrap();
+    assert_eq!(graph.blocks()[loop_block].commands().start, 12);
+    let end_block = graph.block_of(39).unwrap();
+    assert_eq!(graph.blocks()[end_block].commands(), 39..40);
+    assert!(graph
+        .successors(graph.block_of(35).unwrap())
+        .any(|edge| edge.kind() == EdgeKind::Tasty && edge.to() == Target::Block(loop_block)));
+
+    let halts = graph
+        .edges()
+        .iter()
+        .filter(|edge| edge.kind() == EdgeKind::Halt)
+        .count();
+    assert_eq!(halts, 3);
+    let not_tasty_jumps = graph
+        .edges()
+        .iter()
+        .filter(|edge| edge.kind() == EdgeKind::NotTasty && edge.to() == Target::Block(end_block))
+        .count();
+    assert_eq!(not_tasty_jumps, 10);
+}
+
+#[test]
+fn dynamic_edges() {
+    let program = pancakestack::parse_program_str(
+        "Give me a pancake!\n[Loop]\nTake off the butter!\nIf the pancake is tasty, go over to \"Loop\".\nShow me a pancake!",
+    );
+    let graph = ControlFlowGraph::new(&program, LabelSemantics::TopPancake);
+    assert_eq!(graph.blocks().len(), 2);
+    let edges = graph.successors(0).collect::<Vec<_>>();
+    assert_eq!(edges.len(), 2);
+    assert_eq!(edges[0].to(), Target::Unknown);
+    assert!(edges[0].is_dynamic());
+    assert_eq!(edges[1].to(), Target::Block(1));
+    assert_eq!(graph.successors(1).next().unwrap().to(), Target::Exit);
+
+    let dot = graph.to_dot(&program);
+    assert!(dot.contains("    block0 -> unknown [label=\"tasty\", style=dashed];\n"));
+    assert!(dot.contains("    block0 -> block1 [label=\"not tasty\"];\n"));
+    assert!(dot.contains("3: If the pancake is tasty, go over to \\\"Loop\\\".\\l"));
+}
+
+#[test]
+fn labels_pointing_to_next_command() {
+    let program = pancakestack::parse_program_str(
+        "Give me a pancake!\n[Loop]\nTake off the butter!\nIf the pancake is tasty, go over to \"Loop\".\nShow me a pancake!",
+    );
+    let graph = ControlFlowGraph::new(&program, LabelSemantics::NextCommand);
+    assert_eq!(graph.blocks().len(), 3);
+    assert_eq!(graph.blocks()[1].commands(), 2..4);
+    assert!(graph.edges().iter().all(|edge| !edge.is_dynamic()));
+    let edges = graph.successors(1).collect::<Vec<_>>();
+    assert_eq!(edges[0].to(), Target::Block(1));
+    assert_eq!(edges[0].kind(), EdgeKind::Tasty);
+    assert_eq!(edges[1].to(), Target::Block(2));
+}
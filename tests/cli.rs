@@ -0,0 +1,162 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+
+fn pancakestack() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_pancakestack"))
+}
+
+/// Returns a path in the temporary directory that no other test or concurrent test run uses.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pancakestack-cli-{}-{name}", process::id()))
+}
+
+#[test]
+fn run_file() -> Result<(), Box<dyn Error>> {
+    let output = pancakestack()
+        .arg("examples/hello_world.pancake")
+        .stdin(Stdio::null())
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hello World!");
+    Ok(())
+}
+
+#[test]
+fn input_file_and_print_stack() -> Result<(), Box<dyn Error>> {
+    let input = temp_path("input.txt");
+    fs::write(&input, b"ab\x00")?;
+    let output = pancakestack()
+        .args(["examples/cat.pancake", "--print-stack", "--input"])
+        .arg(&input)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"ab\x00");
+    assert_eq!(String::from_utf8(output.stderr)?, "stack: [0]\n");
+    fs::remove_file(&input)?;
+    Ok(())
+}
+
+#[test]
+fn missing_input_file() -> Result<(), Box<dyn Error>> {
+    let output = pancakestack()
+        .args(["examples/cat.pancake", "--input", "examples/missing.txt"])
+        .output()?;
+    assert_eq!(output.status.code(), Some(4));
+    assert!(output.stdout.is_empty());
+    Ok(())
+}
+
+#[test]
+fn error_exit_code() -> Result<(), Box<dyn Error>> {
+    let program = temp_path("error.pancake");
+    fs::write(&program, "Put this good pancake on top!\nTake off the syrup!\nEat the pancake on top!\nEat the pancake on top!\n")?;
+    let output = pancakestack().arg(&program).stdin(Stdio::null()).output()?;
+    assert_eq!(output.status.code(), Some(10));
+    fs::remove_file(&program)?;
+    Ok(())
+}
+
+#[test]
+fn strict_parsing() -> Result<(), Box<dyn Error>> {
+    let program = temp_path("strict.pancake");
+    fs::write(&program, "Put this good pancake on top!\nPut this pancake on top\n\nShow me the pancake!\nShow me a pancake!\n")?;
+
+    let lenient = pancakestack().arg(&program).stdin(Stdio::null()).output()?;
+    assert!(lenient.status.success());
+    assert_eq!(lenient.stdout, b"\x04");
+
+    let strict = pancakestack()
+        .arg("--strict")
+        .arg(&program)
+        .stdin(Stdio::null())
+        .output()?;
+    assert_eq!(strict.status.code(), Some(3));
+    let stderr = String::from_utf8(strict.stderr)?;
+    assert!(stderr.contains("error[E0001]: unknown command"));
+    assert!(stderr.contains("2 | Put this pancake on top\n"));
+    assert!(stderr.contains("4 | Show me the pancake!\n"));
+    assert!(stderr.contains("= help: did you mean \"Show me a pancake!\"?"));
+    fs::remove_file(&program)?;
+    Ok(())
+}
+
+#[test]
+fn usage_error() -> Result<(), Box<dyn Error>> {
+    let output = pancakestack().arg("--unknown").output()?;
+    assert_eq!(output.status.code(), Some(2));
+    Ok(())
+}
+
+#[test]
+fn error_location() -> Result<(), Box<dyn Error>> {
+    let program = temp_path("location.pancake");
+    fs::write(
+        &program,
+        "Put this good pancake on top!\n\nIf the pancake is tasty, go over to \"nowhere\".\n",
+    )?;
+    let output = pancakestack().arg(&program).stdin(Stdio::null()).output()?;
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("error[E0104]: use of undefined label \"nowhere\""));
+    assert!(stderr.contains(".pancake:3:38\n"));
+    fs::remove_file(&program)?;
+    Ok(())
+}
+
+#[test]
+fn emit_rust() -> Result<(), Box<dyn Error>> {
+    let output = pancakestack()
+        .args(["--emit", "rust", "examples/hello_world.pancake"])
+        .output()?;
+    assert!(output.status.success());
+    let source = String::from_utf8(output.stdout)?;
+    assert!(source.contains("fn main()"));
+
+    let output = pancakestack()
+        .args(["--emit", "cobol", "examples/hello_world.pancake"])
+        .output()?;
+    assert_eq!(output.status.code(), Some(2));
+
+    // the generated programs only model labels pointing to the position given by the top pancake
+    let output = pancakestack()
+        .args([
+            "--emit",
+            "c",
+            "--labels",
+            "next",
+            "examples/hello_world.pancake",
+        ])
+        .output()?;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    Ok(())
+}
+
+#[test]
+fn label_semantics() -> Result<(), Box<dyn Error>> {
+    let program = temp_path("labels.pancake");
+    fs::write(
+        &program,
+        "[Start]\nPut this ok pancake on top!\nShow me a pancake!\n",
+    )?;
+
+    let pancake = pancakestack().arg(&program).stdin(Stdio::null()).output()?;
+    assert_eq!(pancake.status.code(), Some(10));
+
+    let next = pancakestack()
+        .args(["--labels", "next"])
+        .arg(&program)
+        .stdin(Stdio::null())
+        .output()?;
+    assert!(next.status.success());
+    assert_eq!(next.stdout, b"\x02");
+
+    let unknown = pancakestack()
+        .args(["--labels", "line"])
+        .arg(&program)
+        .output()?;
+    assert_eq!(unknown.status.code(), Some(2));
+    fs::remove_file(&program)?;
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use pancakestack::Command;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Examples with their inputs, run by every backend.
+const EXAMPLES: [(&str, &[u8]); 4] = [
+    ("examples/hello_world.pancake", b""),
+    ("examples/countdown_bin.pancake", &[20]),
+    ("examples/cat.pancake", "cät\x00".as_bytes()),
+    ("examples/div10.pancake", b"87\n"),
+];
+
+/// Programs failing with different errors, run by every backend.
+const FAILING: [(&str, &[u8]); 7] = [
+    ("Put this good pancake on top!\nShow me a pancake!\nEat the pancake on top!\nEat the pancake on top!", b""),
+    ("Give me a pancake!\nGive me a pancake!", b"12\nnot a number\n"),
+    ("Put this pancake on top!\nTake off the butter!", b""),
+    ("Put this pancake on top!\nPut this a pancake on top!\nPut the top pancakes together!\nIf the pancake is tasty, go over to \"Nowhere\".", b""),
+    ("How about a hotcake?\nPut syrup on the pancakes!\nShow me a pancake!\nTake off the syrup!\nTake off the syrup!", b"a"),
+    ("Put this good pancake on top!\n[Loop]\nPut the top pancakes together!\nPut another pancake on top!\nIf the pancake is tasty, go over to \"Loop\".", b""),
+    ("Put this  pancake on top!\n[Nowhere]\nShow me a pancake!", b""),
+];
+
+fn temp_dir(backend: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("pancakestack-codegen-{backend}-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs the given executable and returns its output and exit code.
+fn run_executable(executable: &Path, input: &[u8]) -> Result<(Vec<u8>, i32), Box<dyn Error>> {
+    let mut child = process::Command::new(executable)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(input)?;
+    let output = child.wait_with_output()?;
+    Ok((output.stdout, output.status.code().unwrap_or(-1)))
+}
+
+/// Returns the output and exit code the `pancakestack` binary produces for the given program.
+fn interpret(program: &[Command<'_>], input: &[u8]) -> Result<(Vec<u8>, i32), Box<dyn Error>> {
+    static PROGRAMS: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "pancakestack-codegen-{}-{}.pancake",
+        process::id(),
+        PROGRAMS.fetch_add(1, Ordering::SeqCst)
+    ));
+    let source = program
+        .iter()
+        .map(|command| format!("{command}\n"))
+        .collect::<String>();
+    fs::write(&path, source)?;
+    let mut child = process::Command::new(env!("CARGO_BIN_EXE_pancakestack"))
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(input)?;
+    let output = child.wait_with_output()?;
+    fs::remove_file(path)?;
+    Ok((output.stdout, output.status.code().unwrap_or(-1)))
+}
+
+/// The source of a program together with its input.
+type Program = (String, &'static [u8]);
+
+/// Returns all examples and failing programs.
+fn programs() -> Result<Vec<Program>, Box<dyn Error>> {
+    let mut programs = Vec::new();
+    for (path, input) in &EXAMPLES {
+        programs.push((fs::read_to_string(path)?, *input));
+    }
+    for (source, input) in &FAILING {
+        programs.push((source.to_string(), *input));
+    }
+    Ok(programs)
+}
+
+/// Checks that the programs compiled by the given function behave like the interpreter.
+fn assert_same_behavior(
+    backend: &str,
+    compile: impl Fn(&[Command<'_>], &Path) -> Result<PathBuf, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let dir = temp_dir(backend);
+    for (index, (source, input)) in programs()?.iter().enumerate() {
+        let program = pancakestack::parse_program_str(source);
+        let executable = compile(&program, &dir.join(format!("program{index}")))?;
+        assert_eq!(
+            run_executable(&executable, input)?,
+            interpret(&program, input)?,
+            "{source}"
+        );
+    }
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn rust_backend() -> Result<(), Box<dyn Error>> {
+    assert_same_behavior("rust", |program, path| {
+        let source = path.with_extension("rs");
+        fs::write(&source, pancakestack::codegen::rust::generate(program))?;
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let status = process::Command::new(rustc)
+            .args(["--edition", "2018", "-O", "-o"])
+            .arg(path)
+            .arg(&source)
+            .status()?;
+        assert!(status.success());
+        Ok(path.to_path_buf())
+    })
+}
+
+#[test]
+fn c_backend() -> Result<(), Box<dyn Error>> {
+    assert_same_behavior("c", |program, path| {
+        let source = path.with_extension("c");
+        fs::write(&source, pancakestack::codegen::c::generate(program))?;
+        let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+        let output = process::Command::new(cc)
+            .args(["-std=c99", "-pedantic", "-Wall", "-Wextra", "-O2", "-o"])
+            .arg(path)
+            .arg(&source)
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+        Ok(path.to_path_buf())
+    })
+}
+
+/// The state of the host running a WebAssembly module.
+struct Host {
+    input: &'static [u8],
+    output: Vec<u8>,
+}
+
+/// Runs the given WebAssembly module and returns its output and exit code.
+fn run_wasm(module: &str, input: &'static [u8]) -> Result<(Vec<u8>, i32), Box<dyn Error>> {
+    use wasmi::{Caller, Engine, Linker, Module, Store};
+
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wat::parse_str(module)?[..])?;
+    let mut store = Store::new(
+        &engine,
+        Host {
+            input,
+            output: Vec::new(),
+        },
+    );
+    let mut linker = Linker::<Host>::new(&engine);
+    linker.func_wrap(
+        "pancakestack",
+        "read_byte",
+        |mut caller: Caller<'_, Host>| -> i32 {
+            let host = caller.data_mut();
+            match host.input.split_first() {
+                Some((&byte, rest)) => {
+                    host.input = rest;
+                    i32::from(byte)
+                }
+                None => 0,
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "pancakestack",
+        "read_number",
+        |mut caller: Caller<'_, Host>| -> i64 {
+            let host = caller.data_mut();
+            let mut line = Vec::new();
+            host.input.read_until(b'\n', &mut line).unwrap();
+            String::from_utf8_lossy(&line)
+                .trim_end_matches(&['\n', '\r'][..])
+                .parse::<u32>()
+                .map_or(-1, i64::from)
+        },
+    )?;
+    linker.func_wrap(
+        "pancakestack",
+        "write_char",
+        |mut caller: Caller<'_, Host>, c: i32| -> i32 {
+            let c = std::char::from_u32(c as u32).unwrap();
+            let mut buffer = [0; 4];
+            let host = caller.data_mut();
+            host.output
+                .extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            0
+        },
+    )?;
+    let instance = linker.instantiate(&mut store, &module)?.start(&mut store)?;
+    let code = instance
+        .get_typed_func::<(), i32>(&store, "run")?
+        .call(&mut store, ())?;
+    Ok((store.into_data().output, code))
+}
+
+#[test]
+fn wat_backend() -> Result<(), Box<dyn Error>> {
+    let mut programs = programs()?;
+    // every command is a nested block, so long programs test the limits of the runtime
+    programs.push((fs::read_to_string("examples/long.pancake")?, b""));
+    for (source, input) in programs {
+        let program = pancakestack::parse_program_str(&source);
+        let module = pancakestack::codegen::wat::generate(&program);
+        assert_eq!(
+            run_wasm(&module, input)?,
+            interpret(&program, input)?,
+            "{source}"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use std::error::Error;
+use std::fs;
+use std::str;
+
+use pancakestack::debug::{Breakpoint, Debugger, Pause, Watchpoint};
+
+#[test]
+fn label_breakpoint() -> Result<(), Box<dyn Error>> {
+    let source = fs::read_to_string("examples/countdown_bin.pancake")?;
+    let mut debugger = Debugger::from_source(&source);
+    debugger.add_breakpoint(Breakpoint::Label("Loop".to_string()));
+
+    let input = [3];
+    let mut output = Vec::new();
+    let pause = debugger.continue_execution(&input[..], &mut output)?;
+    assert_eq!(
+        pause,
+        Pause::Breakpoint(Breakpoint::Label("Loop".to_string()))
+    );
+    assert_eq!(debugger.current_line(), Some(2));
+
+    let pause = debugger.continue_execution(&input[..], &mut output)?;
+    assert_eq!(
+        pause,
+        Pause::Breakpoint(Breakpoint::Label("Loop".to_string()))
+    );
+    assert_eq!(debugger.current_line(), Some(6));
+    assert_eq!(debugger.machine().stack(), &[2]);
+    assert_eq!(output, [3, 2]);
+    Ok(())
+}
+
+#[test]
+fn step_over_loop() -> Result<(), Box<dyn Error>> {
+    let source = fs::read_to_string("examples/countdown_bin.pancake")?;
+    let mut debugger = Debugger::from_source(&source);
+    debugger.add_breakpoint(Breakpoint::Line(8));
+
+    let input = [3];
+    let mut output = Vec::new();
+    let pause = debugger.continue_execution(&input[..], &mut output)?;
+    assert_eq!(pause, Pause::Breakpoint(Breakpoint::Line(8)));
+
+    debugger.remove_breakpoint(&Breakpoint::Line(8));
+    assert_eq!(debugger.step_over(&input[..], &mut output)?, Pause::Stepped);
+    assert_eq!(debugger.current_line(), Some(9));
+    assert_eq!(output, [3, 2, 1, 0]);
+
+    assert_eq!(debugger.step(&input[..], &mut output)?, Pause::Halted);
+    Ok(())
+}
+
+#[test]
+fn watch_stack_depth() -> Result<(), Box<dyn Error>> {
+    let source = fs::read_to_string("examples/cat.pancake")?;
+    let mut debugger = Debugger::from_source(&source);
+    debugger.add_watchpoint(Watchpoint::StackDepth);
+
+    let input = b"a\x00";
+    let mut output = Vec::new();
+    let pause = debugger.continue_execution(&input[..], &mut output)?;
+    assert_eq!(pause, Pause::StackDepthChanged { old: 0, new: 1 });
+    let pause = debugger.continue_execution(&input[..], &mut output)?;
+    assert_eq!(pause, Pause::StackDepthChanged { old: 1, new: 0 });
+    assert_eq!(debugger.current_line(), Some(4));
+
+    debugger.remove_watchpoint(Watchpoint::StackDepth);
+    debugger.add_watchpoint(Watchpoint::TopValue);
+    let pause = debugger.continue_execution(&input[..], &mut output)?;
+    assert_eq!(
+        pause,
+        Pause::TopValueChanged {
+            old: None,
+            new: Some(u32::from(b'a'))
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn interactive_session() -> Result<(), Box<dyn Error>> {
+    let source = fs::read_to_string("examples/hello_world.pancake")?;
+    let mut debugger = Debugger::from_source(&source);
+
+    let commands = b"break 18\ncontinue\nstack\nstep\nwhere\nquit\n";
+    let mut console = Vec::new();
+    let mut output = Vec::new();
+    debugger.run_interactive(&commands[..], &mut console, std::io::empty(), &mut output)?;
+
+    let console = str::from_utf8(&console)?;
+    assert!(console.contains("hit breakpoint at line 18"));
+    assert!(console.contains("   0: 72 'H'"));
+    assert!(console.contains("  19 | Put this delectable pancake on top!"));
+    assert_eq!(output, b"H");
+    Ok(())
+}
+
+#[test]
+fn breakpoint_at_first_command() -> Result<(), Box<dyn Error>> {
+    let source = fs::read_to_string("examples/cat.pancake")?;
+    let mut debugger = Debugger::from_source(&source);
+    debugger.add_breakpoint(Breakpoint::Line(1));
+    debugger.add_breakpoint(Breakpoint::Line(3));
+
+    let input = b"a\x00";
+    let mut output = Vec::new();
+    let pause = debugger.continue_execution(&input[..], &mut output)?;
+    assert_eq!(pause, Pause::Breakpoint(Breakpoint::Line(1)));
+    assert!(debugger.machine().stack().is_empty());
+
+    let pause = debugger.continue_execution(&input[..], &mut output)?;
+    assert_eq!(pause, Pause::Breakpoint(Breakpoint::Line(3)));
+    assert_eq!(debugger.current_line(), Some(3));
+
+    debugger.remove_breakpoint(&Breakpoint::Line(3));
+    assert_eq!(debugger.step(&input[..], &mut output)?, Pause::Stepped);
+    debugger.add_breakpoint(Breakpoint::Line(4));
+    let pause = debugger.continue_execution(&input[..], &mut output)?;
+    assert_eq!(pause, Pause::Breakpoint(Breakpoint::Line(4)));
+    assert_eq!(debugger.machine().steps(), 3);
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use pancakestack::diagnostic::Diagnostic;
+
+#[test]
+fn undefined_label() {
+    let source = "Put this good pancake on top!\n[Loop]\nTake off the butter!\nIf the pancake is tasty, go over to \"Lop\".\n";
+    let program = pancakestack::parse_program_str_spanned(source);
+    let error =
+        pancakestack::run_program_spanned(&program, std::io::empty(), std::io::sink()).unwrap_err();
+
+    let diagnostic = Diagnostic::from_runtime_error(&error, source);
+    assert_eq!(diagnostic.code(), "E0104");
+    assert_eq!(&source[diagnostic.span()], "Lop");
+    assert_eq!(
+        diagnostic.render("loop.pancake", source),
+        "\
+error[E0104]: use of undefined label \"Lop\"
+ --> loop.pancake:4:38
+  |
+4 | If the pancake is tasty, go over to \"Lop\".
+  |                                      ^^^ this label has not been defined yet
+  |
+  = note: the stack was [3] (top pancake last)
+  = help: did you mean \"Loop\"?
+"
+    );
+}
+
+#[test]
+fn label_declared_later() {
+    let source = "Put this good pancake on top!\nIf the pancake is tasty, go over to \"L\".\n[L]\n";
+    let program = pancakestack::parse_program_str_spanned(source);
+    let error =
+        pancakestack::run_program_spanned(&program, std::io::empty(), std::io::sink()).unwrap_err();
+
+    let diagnostic = Diagnostic::from_runtime_error(&error, source);
+    assert_eq!(diagnostic.help(), None);
+    assert!(diagnostic.render("later.pancake", source).contains(
+        "  = note: label \"L\" is declared, but its declaration has not been reached yet\n"
+    ));
+}
+
+#[test]
+fn out_of_pancakes() {
+    let source = "Put this good pancake on top!\r\n\r\nPut the top pancakes together!\r\n";
+    let program = pancakestack::parse_program_str_spanned(source);
+    let error =
+        pancakestack::run_program_spanned(&program, std::io::empty(), std::io::sink()).unwrap_err();
+
+    let rendered = Diagnostic::from_runtime_error(&error, source).render("sum.pancake", source);
+    assert!(rendered.starts_with("error[E0101]: out of pancakes\n --> sum.pancake:3:1\n"));
+    assert!(rendered.contains(
+        "3 | Put the top pancakes together!\n  | ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ this needs 2 pancakes but there is only 1\n"
+    ));
+}
+
+#[test]
+fn no_suggestion_for_gibberish() {
+    let source = "This is not a pancake at all";
+    let diagnostics = pancakestack::parse_program_str_strict(source).unwrap_err();
+    let diagnostic = Diagnostic::from_parse_diagnostic(&diagnostics[0]);
+    assert_eq!(diagnostic.help(), None);
+}
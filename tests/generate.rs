@@ -0,0 +1,44 @@
+use pancakestack::generate::generate_print_program;
+use pancakestack::Command;
+use std::fs;
+
+fn run(program: &[Command<'_>]) -> String {
+    let mut output = Vec::new();
+    pancakestack::run_program(program, std::io::empty(), &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn print_text() {
+    for text in &[
+        "",
+        "a",
+        "Hello World!\n",
+        "aaaabbbcba",
+        "\0\u{1}\u{ff}",
+        "Pfannkuchen, crêpes, パンケーキ 🥞",
+        "\u{10ffff}",
+    ] {
+        assert_eq!(run(&generate_print_program(text)), *text);
+    }
+}
+
+#[test]
+fn shorter_than_example() {
+    let source = fs::read_to_string("examples/hello_world.pancake").unwrap();
+    let example = pancakestack::parse_program_str(&source);
+    let text = run(&example);
+    let program = generate_print_program(&text);
+    assert_eq!(run(&program), text);
+    assert!(program.len() < example.len());
+}
+
+#[test]
+fn parses_back() {
+    let text = "Pancakes 🥞\n";
+    let source = generate_print_program(text)
+        .iter()
+        .map(|command| format!("{command}\n"))
+        .collect::<String>();
+    assert_eq!(run(&pancakestack::parse_program_str(&source)), text);
+}
@@ -0,0 +1,362 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::prelude::*;
+use std::time::Instant;
+
+use pancakestack::{
+    CancellationToken, Command, Continuation, ContinuationError, LabelSemantics, Limits, Machine,
+    Status,
+};
+
+#[test]
+fn step_through_program() -> Result<(), Box<dyn Error>> {
+    let mut file = File::open("examples/cat.pancake")?;
+    let mut program_str = String::new();
+    file.read_to_string(&mut program_str)?;
+    let program = pancakestack::parse_program_str(&program_str);
+
+    let mut output = Vec::new();
+    let mut machine = Machine::new();
+    machine.feed_input(b"pancakes\x00");
+    let mut statuses = Vec::new();
+    while let Some(command) = program.get(machine.program_counter()) {
+        let status = machine.step(command, &mut output)?;
+        statuses.push(status);
+        if status == Status::Halted {
+            break;
+        }
+    }
+
+    assert!(machine.is_halted());
+    assert_eq!(statuses.len(), 2 + 4 * 9 + 1);
+    assert_eq!(statuses[5], Status::Jumped(2));
+    assert_eq!(statuses.last(), Some(&Status::Halted));
+    assert_eq!(machine.stack(), &[0]);
+    assert_eq!(machine.labels().get("CAT"), Some(&2));
+    assert_eq!(output, b"pancakes\x00");
+    Ok(())
+}
+
+#[test]
+fn wait_for_input() -> Result<(), Box<dyn Error>> {
+    let mut output = Vec::new();
+    let mut machine = Machine::new();
+
+    let status = machine.step(&Command::GiveMeAPancake, &mut output)?;
+    assert_eq!(status, Status::WaitingForInput);
+    machine.feed_input(b"4");
+    let status = machine.step(&Command::GiveMeAPancake, &mut output)?;
+    assert_eq!(status, Status::WaitingForInput);
+    assert_eq!(machine.program_counter(), 0);
+
+    machine.feed_input(b"2");
+    machine.close_input();
+    machine.step(&Command::GiveMeAPancake, &mut output)?;
+    machine.step(&Command::HowAboutAHotcake, &mut output)?;
+    assert_eq!(machine.stack(), &[42, 0]);
+    assert_eq!(machine.program_counter(), 2);
+    Ok(())
+}
+
+#[test]
+fn failed_step_keeps_state() {
+    let mut machine = Machine::new();
+    let mut output = Vec::new();
+    machine
+        .step(&Command::PutThisPancakeOnTop("a".into()), &mut output)
+        .unwrap();
+    let result = machine.step(&Command::TakeFromTheTopPancakes, &mut output);
+
+    assert!(matches!(result, Err(pancakestack::Error::OutOfPancakes)));
+    assert_eq!(machine.stack(), &[1]);
+    assert_eq!(machine.program_counter(), 1);
+}
+
+#[test]
+fn runtime_error_context() {
+    let program = pancakestack::parse_program_str(
+        "Put this good pancake on top!\nPut this tasty pancake on top!\n[Loop]\nTake off the syrup!\nTake off the syrup!\nIf the pancake is tasty, go over to \"Loop\".",
+    );
+    let error = Machine::new()
+        .run(&program, std::io::empty(), std::io::sink())
+        .unwrap_err();
+
+    assert!(matches!(
+        error.error(),
+        pancakestack::Error::PancakeUnderflow
+    ));
+    assert_eq!(error.program_counter(), 4);
+    assert_eq!(error.command(), &Command::TakeOffTheSyrup);
+    assert_eq!(error.stack(), &[0, 1]);
+}
+
+#[test]
+fn label_semantics() {
+    let program = pancakestack::parse_program_str(
+        "Put this  pancake on top!\n[Loop]\nPut this ok pancake on top!\nShow me a pancake!",
+    );
+
+    let error = Machine::new()
+        .run(&program, std::io::empty(), std::io::sink())
+        .unwrap_err();
+    assert!(matches!(
+        error.error(),
+        pancakestack::Error::InvalidLabelPosition(label) if label == "Loop"
+    ));
+    assert_eq!(error.program_counter(), 1);
+    assert_eq!(error.stack(), &[0]);
+
+    let mut machine = Machine::with_label_semantics(LabelSemantics::NextCommand);
+    let mut output = Vec::new();
+    machine
+        .run(&program[1..], std::io::empty(), &mut output)
+        .unwrap();
+    assert_eq!(output, b"\x02");
+    assert_eq!(machine.labels()["Loop"], 1);
+}
+
+#[test]
+fn limits() {
+    let program = pancakestack::parse_program_str(
+        "Put this ok pancake on top!\n[Forever]\nShow me a pancake!\nIf the pancake is tasty, go over to \"Forever\".",
+    );
+
+    let mut machine = Machine::new();
+    machine.set_limits(Limits::new().with_max_steps(10));
+    let error = machine
+        .run(&program, std::io::empty(), std::io::sink())
+        .unwrap_err();
+    assert!(matches!(
+        error.error(),
+        pancakestack::Error::StepLimitExceeded(10)
+    ));
+    assert_eq!(machine.steps(), 10);
+    assert_eq!(error.program_counter(), 1);
+
+    let error = pancakestack::run_program_with_limits(
+        &program,
+        std::io::empty(),
+        std::io::sink(),
+        Limits::new().with_max_output_bytes(100),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error,
+        pancakestack::Error::OutputLimitExceeded(100)
+    ));
+
+    let error = pancakestack::run_program_with_limits(
+        &program,
+        std::io::empty(),
+        std::io::sink(),
+        Limits::new().with_deadline(Instant::now()),
+    )
+    .unwrap_err();
+    assert!(matches!(error, pancakestack::Error::DeadlineExceeded));
+
+    let mut machine = Machine::new();
+    machine.set_limits(Limits::new().with_max_stack_depth(1));
+    let mut output = Vec::new();
+    let result = machine.step(&Command::HowAboutAHotcake, &mut output);
+    assert_eq!(result.unwrap(), Status::WaitingForInput);
+    machine.feed_input(b"ab");
+    machine
+        .step(&Command::HowAboutAHotcake, &mut output)
+        .unwrap();
+    let result = machine.step(&Command::HowAboutAHotcake, &mut output);
+    assert!(matches!(
+        result,
+        Err(pancakestack::Error::StackLimitExceeded(1))
+    ));
+    assert_eq!(machine.stack(), &[97]);
+    assert_eq!(machine.pending_input(), b"b");
+}
+
+#[test]
+fn pause_and_resume() -> Result<(), Box<dyn Error>> {
+    let mut file = File::open("examples/cat.pancake")?;
+    let mut program_str = String::new();
+    file.read_to_string(&mut program_str)?;
+    let program = pancakestack::parse_program_str(&program_str);
+    let input = "pancakes ".repeat(400);
+
+    let token = CancellationToken::new();
+    let mut machine = Machine::new();
+    machine.set_cancellation_token(token.clone());
+    machine.feed_input(input.as_bytes());
+    machine.close_input();
+    let mut output = Vec::new();
+
+    token.pause();
+    machine.run(&program, std::io::empty(), &mut output)?;
+    assert!(machine.is_paused());
+    assert!(!token.is_pause_requested());
+    assert_eq!(machine.steps(), 0);
+
+    machine.step(&program[0], &mut output)?;
+    token.pause();
+    machine.run(&program, std::io::empty(), &mut output)?;
+    assert!(machine.is_paused());
+    assert_eq!(machine.steps(), 1024);
+
+    let continuation = machine.continuation();
+    let bytes = continuation.to_bytes();
+    assert_eq!(Continuation::from_bytes(&bytes), Ok(continuation.clone()));
+    let mut resumed = Machine::from_continuation(continuation);
+    resumed.run(&program, std::io::empty(), &mut output)?;
+    assert!(resumed.is_halted());
+    assert!(!resumed.is_paused());
+    assert_eq!(output, format!("{input}\x00").as_bytes());
+    Ok(())
+}
+
+#[test]
+fn cancel() {
+    let program = pancakestack::parse_program_str("Put this good pancake on top!");
+    let token = CancellationToken::new();
+    let mut machine = Machine::new();
+    machine.set_cancellation_token(token.clone());
+    token.cancel();
+    token.pause();
+    assert!(token.is_cancelled());
+    assert!(!token.is_pause_requested());
+
+    let error = machine
+        .run(&program, std::io::empty(), std::io::sink())
+        .unwrap_err();
+    assert!(matches!(error.error(), pancakestack::Error::Cancelled));
+    assert_eq!(error.program_counter(), 0);
+    assert!(machine.stack().is_empty());
+}
+
+#[test]
+fn cancel_restored_machine() -> Result<(), Box<dyn Error>> {
+    let program =
+        pancakestack::parse_program_str("Put this good pancake on top!\nShow me a pancake!");
+    let mut machine = Machine::new();
+    machine.step(&program[0], &mut std::io::sink())?;
+    let snapshot = machine.snapshot();
+
+    // the token is checked before the first command after restoring, even though 1 step was restored
+    let token = CancellationToken::new();
+    let mut restored = Machine::new();
+    restored.set_cancellation_token(token.clone());
+    token.cancel();
+    restored.restore(&snapshot)?;
+    assert_eq!(restored.steps(), 1);
+    let error = restored
+        .run(&program, std::io::empty(), std::io::sink())
+        .unwrap_err();
+    assert!(matches!(error.error(), pancakestack::Error::Cancelled));
+    assert_eq!(error.program_counter(), 1);
+    Ok(())
+}
+
+#[test]
+fn invalid_continuation() {
+    let mut machine = Machine::with_label_semantics(LabelSemantics::NextCommand);
+    machine.feed_input(b"42\n");
+    machine
+        .run(
+            &pancakestack::parse_program_str("[Start]\nPut this good pancake on top!"),
+            std::io::empty(),
+            std::io::sink(),
+        )
+        .unwrap();
+    let bytes = machine.continuation().to_bytes();
+    let continuation = Continuation::from_bytes(&bytes).unwrap();
+    let restored = Machine::from_continuation(continuation);
+    assert_eq!(restored.label_semantics(), LabelSemantics::NextCommand);
+    assert_eq!(restored.labels()["Start"], 1);
+    assert_eq!(restored.pending_input(), b"42\n");
+
+    for length in 0..bytes.len() {
+        assert_eq!(
+            Continuation::from_bytes(&bytes[..length]),
+            Err(ContinuationError::InvalidFormat)
+        );
+    }
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+        Continuation::from_bytes(&trailing),
+        Err(ContinuationError::InvalidFormat)
+    );
+    let mut version = bytes;
+    version[4] = 99;
+    assert_eq!(
+        Continuation::from_bytes(&version),
+        Err(ContinuationError::UnsupportedVersion(99))
+    );
+}
+
+#[test]
+fn snapshot_long_program() -> Result<(), Box<dyn Error>> {
+    let source = std::fs::read_to_string("examples/long.pancake")?;
+    let program = pancakestack::parse_program_str(&source);
+
+    let mut expected_output = Vec::new();
+    let mut expected = Machine::new();
+    expected.run(&program, std::io::empty(), &mut expected_output)?;
+
+    let mut output = Vec::new();
+    let mut snapshot = Machine::new().snapshot();
+    let mut checkpoints = 0;
+    loop {
+        let mut machine = Machine::new();
+        machine.restore(&snapshot)?;
+        machine.set_limits(Limits::new().with_max_steps(machine.steps() + 1000));
+        match machine.run(&program, std::io::empty(), &mut output) {
+            Ok(()) => {
+                assert_eq!(machine.snapshot(), expected.snapshot());
+                break;
+            }
+            Err(error) => {
+                assert!(matches!(
+                    error.error(),
+                    pancakestack::Error::StepLimitExceeded(_)
+                ));
+                snapshot = machine.snapshot();
+                checkpoints += 1;
+            }
+        }
+    }
+    assert_eq!(checkpoints, expected.steps() / 1000);
+    assert_eq!(output, expected_output);
+    Ok(())
+}
+
+#[test]
+fn snapshot_keeps_labels_and_input() -> Result<(), Box<dyn Error>> {
+    let mut file = File::open("examples/cat.pancake")?;
+    let mut program_str = String::new();
+    file.read_to_string(&mut program_str)?;
+    let program = pancakestack::parse_program_str(&program_str);
+
+    let mut machine = Machine::new();
+    machine.feed_input(b"pancakes");
+    for _ in 0..10 {
+        machine.step(&program[machine.program_counter()], &mut Vec::new())?;
+    }
+    let snapshot = machine.snapshot();
+
+    let mut restored = Machine::new();
+    restored.set_limits(Limits::new().with_max_output_bytes(100));
+    assert_eq!(
+        restored.restore(&snapshot[1..]),
+        Err(ContinuationError::InvalidFormat)
+    );
+    assert_eq!(restored.steps(), 0);
+    restored.restore(&snapshot)?;
+    assert_eq!(restored.snapshot(), snapshot);
+    assert_eq!(restored.labels(), machine.labels());
+    assert_eq!(restored.pending_input(), b"ncakes");
+    assert_eq!(restored.limits().max_output_bytes(), Some(100));
+
+    let mut output = Vec::new();
+    restored.feed_input(b"\x00");
+    restored.run(&program, std::io::empty(), &mut output)?;
+    assert!(restored.is_halted());
+    assert_eq!(output, b"ncakes\x00");
+    Ok(())
+}
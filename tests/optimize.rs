@@ -0,0 +1,155 @@
+use pancakestack::bytecode::{Bytecode, Op};
+use pancakestack::optimize::optimize;
+use pancakestack::{Command, Machine, Status};
+use std::error::Error;
+use std::fs;
+
+#[test]
+fn optimize_long() -> Result<(), Box<dyn Error>> {
+    let source = fs::read_to_string("examples/long.pancake")?;
+    let bytecode = optimize(&pancakestack::parse_program_str(&source));
+    assert_eq!(bytecode.ops()[..3], [Op::Push(4), Op::Print, Op::Halt]);
+
+    let mut output = Vec::new();
+    bytecode.run(std::io::empty(), &mut output)?;
+    assert_eq!(output, b"\x04");
+    Ok(())
+}
+
+#[test]
+fn keep_errors() {
+    let programs = [
+        // the second flip fails even though two flips do nothing
+        "Put this good pancake on top!\nFlip the pancakes on top!\nFlip the pancakes on top!",
+        "Put this good pancake on top!\nTake off the butter!\nPut butter on the pancakes!\nTake off the butter!\nTake off the butter!\nTake off the butter!\nTake off the butter!\nTake off the butter!\nShow me a pancake!",
+        "Put this pancake on top!\nTake off the butter!\nPut butter on the pancakes!\nShow me a pancake!",
+        "Put this good pancake on top!\nEat the pancake on top!\nEat the pancake on top!",
+    ];
+    for program in &programs {
+        let program = pancakestack::parse_program_str(program);
+        let expected = pancakestack::run_program(&program, std::io::empty(), std::io::sink())
+            .unwrap_err()
+            .to_string();
+        let error = optimize(&program)
+            .run(std::io::empty(), std::io::sink())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, expected);
+    }
+}
+
+#[test]
+fn jump_into_fused_ops() -> Result<(), Box<dyn Error>> {
+    // "Middle" points to the second butter, which is fused with the others
+    let program = pancakestack::parse_program_str(
+        "\
+Put this a pancake on top!
+Put this sevenss pancake on top!
+[Middle]
+Put this a pancake on top!
+Eat the pancake on top!
+Put butter on the pancakes!
+Put butter on the pancakes!
+Put butter on the pancakes!
+Show me a pancake!
+Flip the pancakes on top!
+Take off the butter!
+If the pancake isn't tasty, go over to \"Middle\".
+Eat all of the pancakes!",
+    );
+    let mut expected = Vec::new();
+    pancakestack::run_program(&program, std::io::empty(), &mut expected)?;
+    assert_eq!(expected, b"\n\x02");
+
+    let mut output = Vec::new();
+    optimize(&program).run(std::io::empty(), &mut output)?;
+    assert_eq!(output, expected);
+    Ok(())
+}
+
+/// A small xorshift generator to create the same programs on every run.
+struct Random(u64);
+
+impl Random {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+fn random_program(random: &mut Random) -> Vec<Command<'static>> {
+    const ADJECTIVES: [&str; 4] = ["", "a", "ab", "good"];
+    const LABELS: [&str; 3] = ["A", "B", "C"];
+    let length = 1 + random.below(40);
+    let mut program = Vec::new();
+    while program.len() < length {
+        let command = match random.below(20) {
+            0..=2 => Command::PutThisPancakeOnTop(ADJECTIVES[random.below(4)].into()),
+            3..=4 => Command::EatThePancakeOnTop,
+            5..=6 => Command::PutButterOnThePancakes,
+            7..=8 => Command::TakeOffTheButter,
+            9 => Command::FlipThePancakesOnTop,
+            10 => Command::PutAnotherPancakeOnTop,
+            11 => Command::PutTheTopPancakesTogether,
+            12 => Command::TakeFromTheTopPancakes,
+            13 => Command::ShowMeAPancake,
+            14 => Command::HowAboutAHotcake,
+            15 => Command::Label(LABELS[random.below(3)].into()),
+            16 => Command::IfThePancakeIsntTastyGoOverTo(LABELS[random.below(3)].into()),
+            17 => Command::IfThePancakeIsTastyGoOverTo(LABELS[random.below(3)].into()),
+            18 => Command::PutSyrupOnThePancakes,
+            _ => Command::TakeOffTheSyrup,
+        };
+        program.push(command);
+    }
+    program
+}
+
+/// Runs the program for at most the given number of steps, returning `None` if it did not finish or defined a label on a zero pancake.
+fn run_limited(program: &[Command<'_>], input: &[u8], steps: usize) -> Option<(Vec<u8>, String)> {
+    let mut machine = Machine::new();
+    machine.feed_input(input);
+    machine.close_input();
+    let mut output = Vec::new();
+    for _ in 0..steps {
+        let command = match program.get(machine.program_counter()) {
+            Some(command) => command,
+            None => return Some((output, String::new())),
+        };
+        if let (Command::Label(_), Some(0)) = (command, machine.stack().last()) {
+            return None;
+        }
+        match machine.step(command, &mut output) {
+            Ok(Status::Halted) => return Some((output, String::new())),
+            Ok(_) => {}
+            Err(error) => return Some((output, error.to_string())),
+        }
+    }
+    None
+}
+
+#[test]
+fn differential() {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    let mut finished = 0;
+    for _ in 0..5000 {
+        let program = random_program(&mut random);
+        let expected = match run_limited(&program, b"\x03\x00", 10_000) {
+            Some(expected) => expected,
+            None => continue,
+        };
+        finished += 1;
+
+        for bytecode in &[Bytecode::compile(&program), optimize(&program)] {
+            let mut output = Vec::new();
+            let error = bytecode
+                .run(&b"\x03\x00"[..], &mut output)
+                .err()
+                .map_or(String::new(), |error| error.to_string());
+            assert_eq!((output, error), expected, "{program:#?}");
+        }
+    }
+    assert!(finished > 4000);
+}
@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::process;
+use std::rc::Rc;
+use std::str;
+
+use pancakestack::repl::Repl;
+
+#[test]
+fn jump_back_replays_entered_lines() -> Result<(), Box<dyn Error>> {
+    let mut repl = Repl::new();
+    let mut output = Vec::new();
+    for line in [
+        "Put this old pancake on top!",
+        "[CAT]",
+        "Eat the pancake on top!",
+        "How about a hotcake?",
+        "Show me a pancake!",
+        "If the pancake is tasty, go over to \"CAT\".",
+    ] {
+        repl.eval_line(line, &b"cat\x00"[..], &mut output)?;
+    }
+    assert_eq!(output, b"cat\x00");
+    assert_eq!(repl.machine().stack(), &[0]);
+    assert_eq!(repl.history().len(), 6);
+    Ok(())
+}
+
+#[test]
+fn failed_command_is_forgotten() {
+    let mut repl = Repl::new();
+    let result = repl.eval_line("Eat the pancake on top!", std::io::empty(), Vec::new());
+    assert!(result.is_err());
+    assert!(repl.history().is_empty());
+    assert!(repl
+        .eval_line("Eat the pancake", std::io::empty(), Vec::new())
+        .is_err());
+}
+
+/// A terminal echoing each line of the session once the repl starts reading it, like a user typing it.
+struct Terminal {
+    session: Vec<u8>,
+    position: usize,
+    echoed: usize,
+    transcript: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Read for Terminal {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = self.fill_buf()?.read(buf)?;
+        self.consume(length);
+        Ok(length)
+    }
+}
+
+impl BufRead for Terminal {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position == self.echoed {
+            let rest = &self.session[self.position..];
+            let line_length = rest
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(rest.len(), |index| index + 1);
+            self.transcript
+                .borrow_mut()
+                .extend_from_slice(&rest[..line_length]);
+            self.echoed += line_length;
+        }
+        Ok(&self.session[self.position..self.echoed])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position += amount;
+    }
+}
+
+/// The screen of a [`Terminal`], showing the output of the repl.
+struct Screen(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn interactive_session() -> Result<(), Box<dyn Error>> {
+    let transcript = Rc::new(RefCell::new(Vec::new()));
+    let terminal = Terminal {
+        session: b"Give me a pancake!\n65\nShow me a pancake!\n:labels\n:load examples/hello_world.pancake\n:reset\n:stack\n:quit\n".to_vec(),
+        position: 0,
+        echoed: 0,
+        transcript: Rc::clone(&transcript),
+    };
+    Repl::new().run(terminal, Screen(Rc::clone(&transcript)))?;
+    let transcript = transcript.borrow();
+    let transcript = str::from_utf8(&transcript)?;
+
+    assert!(transcript.starts_with("> Give me a pancake!\ninput> 65\nstack: [65]\n"));
+    assert!(transcript.contains("> Show me a pancake!\nA\nstack: [65]\n"));
+    assert!(transcript.contains("(no labels)"));
+    assert!(transcript.contains("Hello World!\nall pancakes were eaten\n"));
+    assert!(transcript.ends_with("> :stack\nstack: []\n> :quit\n"));
+    Ok(())
+}
+
+#[test]
+fn output_before_input_prompt() -> Result<(), Box<dyn Error>> {
+    let program = std::env::temp_dir().join(format!("pancakestack-repl-{}.pancake", process::id()));
+    fs::write(
+        &program,
+        "Put this good pancake on top!\nShow me a pancake!\nGive me a pancake!\n",
+    )?;
+    let transcript = Rc::new(RefCell::new(Vec::new()));
+    let terminal = Terminal {
+        session: format!(":load {}\n7\n", program.display()).into_bytes(),
+        position: 0,
+        echoed: 0,
+        transcript: Rc::clone(&transcript),
+    };
+    Repl::new().run(terminal, Screen(Rc::clone(&transcript)))?;
+    fs::remove_file(&program)?;
+    let transcript = transcript.borrow();
+    assert!(str::from_utf8(&transcript)?.ends_with(".pancake\n\x04\ninput> 7\nstack: [4, 7]\n> "));
+    Ok(())
+}
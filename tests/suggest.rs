@@ -0,0 +1,73 @@
+use pancakestack::suggest::suggest;
+use pancakestack::Command;
+
+#[test]
+fn suggest_fixed_command() {
+    let error = Command::from_line("Eat all the pancakes!").unwrap_err();
+    assert_eq!(error.suggestion(), Some(Command::EatAllOfThePancakes));
+
+    let error = Command::from_line("put syrup on the pancake").unwrap_err();
+    assert_eq!(error.suggestion(), Some(Command::PutSyrupOnThePancakes));
+}
+
+#[test]
+fn suggest_parameterized_command() {
+    let error = Command::from_line("Put this delicious pancake ontop!").unwrap_err();
+    assert_eq!(
+        error.suggestion(),
+        Some(Command::PutThisPancakeOnTop("delicious".into()))
+    );
+
+    let error = Command::from_line("Put this very good pancake on top!").unwrap_err();
+    assert_eq!(
+        error.suggestion(),
+        Some(Command::PutThisPancakeOnTop("very-good".into()))
+    );
+
+    let error = Command::from_line("[Loop").unwrap_err();
+    assert_eq!(error.suggestion(), Some(Command::Label("Loop".into())));
+
+    let error = Command::from_line("If the pancake is tasty go over to \"Loop\".").unwrap_err();
+    assert_eq!(
+        error.suggestion(),
+        Some(Command::IfThePancakeIsTastyGoOverTo("Loop".into()))
+    );
+
+    let error = Command::from_line("If the pancake isnt tasty, go over to \"End\"").unwrap_err();
+    assert_eq!(
+        error.suggestion(),
+        Some(Command::IfThePancakeIsntTastyGoOverTo("End".into()))
+    );
+}
+
+#[test]
+fn suggest_nothing_for_unrelated_lines() {
+    for line in &[
+        "This is not a pancake at all.",
+        "Waffles are better.",
+        "",
+        "fn main() {}",
+    ] {
+        assert_eq!(suggest(line), Vec::new(), "{line}");
+    }
+}
+
+#[test]
+fn suggestions_are_ordered() {
+    let suggestions = suggest("Take off the butte!");
+    assert_eq!(suggestions[0].command(), &Command::TakeOffTheButter);
+    assert_eq!(suggestions[0].distance(), 1);
+    assert!(suggestions
+        .windows(2)
+        .all(|pair| pair[0].distance() <= pair[1].distance()));
+}
+
+#[test]
+fn suggest_for_long_lines() {
+    let label = "pancake".repeat(500);
+    let line = format!("[{label}");
+    let error = Command::from_line(&line).unwrap_err();
+    assert_eq!(error.suggestion(), Some(Command::Label(label.into())));
+
+    assert_eq!(suggest(&"x".repeat(3000)), Vec::new());
+}
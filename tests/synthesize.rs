@@ -0,0 +1,82 @@
+use pancakestack::optimize::optimize_constants;
+use pancakestack::synthesize::{synthesize, Synthesizer};
+use pancakestack::{Command, Machine};
+use std::fs;
+
+/// Runs the commands on an empty stack and returns the stack afterwards.
+fn stack(commands: &[Command<'_>]) -> Vec<u32> {
+    let mut machine = Machine::new();
+    machine
+        .run(commands, &mut std::io::empty(), &mut std::io::sink())
+        .unwrap();
+    machine.stack().to_vec()
+}
+
+#[test]
+fn small_numbers() {
+    let mut synthesizer = Synthesizer::new();
+    for target in 0..=2000 {
+        let commands = synthesizer.synthesize(target);
+        assert_eq!(commands.len(), synthesizer.cost(target));
+        assert_eq!(stack(&commands), [target], "{target}");
+    }
+    assert_eq!(synthesizer.cost(13), 1);
+    assert_eq!(synthesizer.cost(26), 3);
+    assert_eq!(synthesizer.cost(1024), 15);
+}
+
+#[test]
+fn large_numbers() {
+    let mut synthesizer = Synthesizer::new();
+    let mut random = 0x2545_f491_4f6c_dd1d_u64;
+    for _ in 0..20 {
+        random ^= random << 13;
+        random ^= random >> 7;
+        random ^= random << 17;
+        let target = random as u32;
+        let commands = synthesizer.synthesize(target);
+        assert_eq!(stack(&commands), [target], "{target}");
+        assert!(commands.len() < 70, "{target}: {}", commands.len());
+    }
+    for &target in &[u32::MAX, u32::MAX - 13, 1 << 31, 0x10_ffff] {
+        assert_eq!(stack(&synthesize(target)), [target]);
+    }
+}
+
+#[test]
+fn keeps_stack() {
+    let mut commands = vec![
+        Command::PutThisPancakeOnTop("rich".into()),
+        Command::PutThisPancakeOnTop("".into()),
+    ];
+    commands.extend(synthesize(123_456));
+    assert_eq!(stack(&commands), [4, 0, 123_456]);
+}
+
+#[test]
+fn optimize_example() {
+    let source = fs::read_to_string("examples/hello_world.pancake").unwrap();
+    let program = pancakestack::parse_program_str(&source);
+    let optimized = optimize_constants(&program);
+    assert!(optimized.len() < program.len());
+
+    let mut expected = Vec::new();
+    pancakestack::run_program(&program, std::io::empty(), &mut expected).unwrap();
+    let mut output = Vec::new();
+    pancakestack::run_program(&optimized, std::io::empty(), &mut output).unwrap();
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn optimize_keeps_errors_and_labels() {
+    let program = pancakestack::parse_program_str(
+        "Put this  pancake on top!\nTake off the butter!\nPut this pancake on top!\nPut this pancake on top!\nPut the top pancakes together!",
+    );
+    let optimized = optimize_constants(&program);
+    assert_eq!(optimized[..2], program[..2]);
+
+    let program = pancakestack::parse_program_str(
+        "Put this delicious pancake on top!\nPut this delicious pancake on top!\nPut the top pancakes together!\n[Label]\nShow me a pancake!",
+    );
+    assert_eq!(optimize_constants(&program), program);
+}